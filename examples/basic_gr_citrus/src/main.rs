@@ -13,63 +13,9 @@
 
 use rx_supplementary_builtins as _;
 
-#[no_mangle]
-#[link_section = ".text.start"]
-#[naked]
-unsafe extern "C" fn start() -> ! {
-    unsafe {
-        core::arch::asm!(
-            "
-                # Initialize .data
-                mov #__sidata, r2
-                mov #__sdata, r1
-                mov #(__edata - __sdata), r3
-                smovf
-
-                # Initialize .bss
-                mov #__sbss, r1
-                mov #(__ebss - __sbss), r3
-                mov #0, r2
-                sstr
-
-                # Set the stack pointers. The initial USP is only used during
-                # the boot phase, so it can be identical to ISP.
-                mvtc #_stack_start, usp
-                mvtc #_stack_start, isp
-
-                mvtc #0, fpsw
-
-                bra _{main}
-            ",
-            main = sym main,
-            options(noreturn)
-        );
-    }
-}
-
-#[no_mangle]
-unsafe extern "C" fn main() -> ! {
-    unsafe {
-        core::arch::asm!(
-            "
-                # Select USP
-                setpsw u
-            ",
-        );
-    }
-    unsafe { <SystemTraits as port::EntryPoint>::start() };
-}
+port::use_startup!(unsafe SystemTraits);
 
-// FIXME: Why does `core::intrinsics::const_eval_select::<(&str, usize, usize),
-// core::str::slice_error_fail_ct, core::str::slice_error_fail_rt, !>` contain
-// a reference to this symbol?
-core::arch::global_asm!(
-    "
-    .global _abort
-_abort:
-    bra _abort
-"
-);
+impl port::StartupOptions for SystemTraits {}
 
 // Panic handler
 // -----------------------------------------------------------------------
@@ -88,7 +34,11 @@ type System = r3_kernel::System<SystemTraits>;
 port::use_port!(unsafe struct SystemTraits);
 port::use_cmt!(unsafe impl PortTimer for SystemTraits);
 
-impl port::ThreadingOptions for SystemTraits {}
+impl port::ThreadingOptions for SystemTraits {
+    const ICU_VARIANT: port::IcuVariant = port::IcuVariant::Icub;
+    const INTERRUPT_LINE_IPR_INDEX: Option<fn(r3::kernel::InterruptNum) -> Option<usize>> =
+        Some(device::ipr_index);
+}
 
 impl port::CmtOptions for SystemTraits {
     const FREQUENCY: u64 = 48_000_000;
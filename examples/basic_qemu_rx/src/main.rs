@@ -8,83 +8,14 @@
 #![no_main]
 #![deny(unsafe_op_in_unsafe_fn)]
 
-// Fixed vector table
-// -----------------------------------------------------------------------
-
-#[link_section = ".fixed_vector_table"]
-#[used]
-static _FIXED_VECTOR_TABLE: [unsafe extern "C" fn() -> !; 32] = {
-    let mut table = [unhandled_exception as _; 32];
-    table[31] = start as _;
-    table
-};
-
-unsafe extern "C" fn unhandled_exception() -> ! {
-    panic!("unhandled exception")
-}
-
 // Startup
 // -----------------------------------------------------------------------
 
 use rx_supplementary_builtins as _;
 
-#[no_mangle]
-#[link_section = ".text.start"]
-#[naked]
-unsafe extern "C" fn start() -> ! {
-    unsafe {
-        core::arch::asm!(
-            "
-                # Initialize .data
-                mov #__sidata, r2
-                mov #__sdata, r1
-                mov #(__edata - __sdata), r3
-                smovf
-
-                # Initialize .bss
-                mov #__sbss, r1
-                mov #(__ebss - __sbss), r3
-                mov #0, r2
-                sstr
-
-                # Set the stack pointers. The initial USP is only used during
-                # the boot phase, so it can be identical to ISP.
-                mvtc #_stack_start, usp
-                mvtc #_stack_start, isp
-
-                mvtc #0, fpsw
-
-                bra _{main}
-            ",
-            main = sym main,
-            options(noreturn)
-        );
-    }
-}
-
-#[no_mangle]
-unsafe extern "C" fn main() -> ! {
-    unsafe {
-        core::arch::asm!(
-            "
-                # Select USP
-                setpsw u
-            ",
-        );
-    }
-    unsafe { <SystemTraits as port::EntryPoint>::start() };
-}
+port::use_startup!(unsafe SystemTraits);
 
-// FIXME: Why does `core::intrinsics::const_eval_select::<(&str, usize, usize),
-// core::str::slice_error_fail_ct, core::str::slice_error_fail_rt, !>` contain
-// a reference to this symbol?
-core::arch::global_asm!(
-    "
-    .global _abort
-_abort:
-    bra _abort
-    "
-);
+impl port::StartupOptions for SystemTraits {}
 
 // Panic handler
 // -----------------------------------------------------------------------
@@ -29,6 +29,22 @@ use r3_core::kernel::{InterruptNum, InterruptPriority};
 /// }
 /// ```
 ///
+/// This driver uses both channels of a CMT instance: one generates timer
+/// interrupts, and the other serves as a free-running counter. The following
+/// example uses CMT2/3 instead of CMT0/1, with the roles of the channels
+/// swapped (interrupt: CMT3, counter: CMT2):
+///
+/// ```rust,ignore
+/// impl r3_port_rx::CmtOptions for SystemTraits {
+///     const CMT_BASE: *mut () = 0x0008_8010 as _;
+///     const INTERRUPT_CHANNEL: usize = 1;
+///     const COUNTER_CHANNEL: usize = 0;
+///     const INTERRUPT_NUM: InterruptNum = 31;
+///     const COUNTER_INTERRUPT_NUM: InterruptNum = 30;
+///     /* ... */
+/// }
+/// ```
+///
 /// # Safety
 ///
 ///  - `CmtOptions` must be configured correctly.
@@ -97,13 +113,27 @@ macro_rules! use_cmt {
     };
 }
 
-/// The options for [`use_cmt!`].
+/// The options for [`use_cmt!`] and [`use_cmt_periodic!`].
+///
+/// [`use_cmt_periodic!`]: crate::use_cmt_periodic
 pub trait CmtOptions {
     /// The base address of the memory-mapped registers exposed by a Compare
-    /// Match Timer instance. Defaults to `0x0008_8000` (CMT0/1) when
-    /// unspecified.
+    /// Match Timer instance (a channel pair). Defaults to `0x0008_8000`
+    /// (CMT0/1) when unspecified. Use `0x0008_8010` for CMT2/3.
     const CMT_BASE: *mut () = 0x0008_8000 as _;
 
+    /// The channel (`0` or `1` within [`Self::CMT_BASE`]) generating timer
+    /// interrupts. Defaults to `0`.
+    const INTERRUPT_CHANNEL: usize = 0;
+
+    /// The channel (`0` or `1` within [`Self::CMT_BASE`]) used as a
+    /// free-running counter by [`use_cmt!`]. Must be different from
+    /// [`Self::INTERRUPT_CHANNEL`]. Defaults to `1`. Not used by
+    /// [`use_cmt_periodic!`].
+    ///
+    /// [`use_cmt_periodic!`]: crate::use_cmt_periodic
+    const COUNTER_CHANNEL: usize = 1;
+
     /// The numerator of the effective input clock rate (usually PCLK) of the
     /// timer unit. This will be further divided by [`Self::PREDIVIDER`] to
     /// determine the actual timer clock.
@@ -115,13 +145,41 @@ pub trait CmtOptions {
 
     /// Set the divider ratio for the predivider (`CMCR.CKS`). Must be one of
     /// `[8, 32, 128, 512]`.
-    const PREDIVIDER: u64;
+    ///
+    /// Defaults to the value chosen based on [`Self::TARGET_RESOLUTION_NS`] and
+    /// [`Self::TARGET_MAX_INTERVAL_US`]. At least one of them must be specified
+    /// if this option is not specified.
+    const PREDIVIDER: u64 = choose_predivider(
+        Self::FREQUENCY,
+        Self::FREQUENCY_DENOMINATOR,
+        Self::TARGET_RESOLUTION_NS,
+        Self::TARGET_MAX_INTERVAL_US,
+        16,
+    );
+
+    /// The desired maximum length of one hardware timer cycle, measured in
+    /// nanoseconds. Used to choose the default value of [`Self::PREDIVIDER`].
+    ///
+    /// If this is the only one specified, the largest divider ratio (which
+    /// minimizes the number of interrupts) meeting this requirement is chosen.
+    /// Defaults to `None`.
+    const TARGET_RESOLUTION_NS: Option<u64> = None;
+
+    /// The desired minimum length of the longest interval a CMT channel can
+    /// measure (2¹⁶ hardware timer cycles), measured in microseconds. Used to
+    /// choose the default value of [`Self::PREDIVIDER`].
+    ///
+    /// The smallest divider ratio (which maximizes the resolution) meeting
+    /// this requirement and [`Self::TARGET_RESOLUTION_NS`] is chosen. Defaults
+    /// to `None`.
+    const TARGET_MAX_INTERVAL_US: Option<u64> = None;
 
     /// The maximum permissible timer interrupt latency, measured in hardware
     /// timer cycles.
     ///
     /// Defaults to `min(FREQUENCY / FREQUENCY_DENOMINATOR / PREDIVIDER / 100,
-    /// 0x8000)` (10 milliseconds maximum).
+    /// 0x8000)` (10 milliseconds maximum), which follows [`Self::PREDIVIDER`]
+    /// even if it's chosen automatically.
     const HEADROOM: u16 = min128(
         Self::FREQUENCY as u128
             / Self::FREQUENCY_DENOMINATOR as u128
@@ -130,22 +188,250 @@ pub trait CmtOptions {
         0x8000,
     ) as u16;
 
-    /// The interrupt number of the first channel of the specified CMT instance.
-    /// Defaults to `28` (CMT0).
+    /// The tick period of [`use_cmt_periodic!`], measured in hardware timer
+    /// cycles. Must be in range `1..=0x10000`. Not used by [`use_cmt!`].
+    ///
+    /// Defaults to `min(FREQUENCY / FREQUENCY_DENOMINATOR / PREDIVIDER / 1000,
+    /// 0x10000)` (1 millisecond maximum).
+    ///
+    /// [`use_cmt_periodic!`]: crate::use_cmt_periodic
+    const TICK_PERIOD: u32 = min128(
+        Self::FREQUENCY as u128
+            / Self::FREQUENCY_DENOMINATOR as u128
+            / Self::PREDIVIDER as u128
+            / 1000,
+        0x10000,
+    ) as u32;
+
+    /// The interrupt number of [`Self::INTERRUPT_CHANNEL`]. Defaults to `28`
+    /// (CMT0).
     const INTERRUPT_NUM: InterruptNum = 28;
 
-    /// The IPR register used to set the specified interrupt line's
-    /// priority. Defaults to `Some(4)`.
-    const IPR_INDEX: Option<usize> = Some(4);
+    /// The interrupt number of [`Self::COUNTER_CHANNEL`]. [`use_cmt!`] defines
+    /// this interrupt line as disabled, which prevents the application from
+    /// using it by accident. Defaults to `29` (CMT1). Not used by
+    /// [`use_cmt_periodic!`].
+    ///
+    /// [`use_cmt_periodic!`]: crate::use_cmt_periodic
+    const COUNTER_INTERRUPT_NUM: InterruptNum = 29;
+
+    /// The IPR register used to set the priority of [`Self::INTERRUPT_NUM`].
+    /// Defaults to `Some(4 + (INTERRUPT_NUM - 28))` (`IPR04`–`IPR07`) if
+    /// [`Self::INTERRUPT_NUM`] is in range `28..=31` (CMT0–CMT3) or `None`
+    /// otherwise.
+    ///
+    /// If this is `None`, [`Self::INTERRUPT_PRIORITY`] is not applied, and
+    /// the application must set the priority of the interrupt line.
+    const IPR_INDEX: Option<usize> = match Self::INTERRUPT_NUM {
+        num @ 28..=31 => Some(4 + (num - 28)),
+        _ => None,
+    };
 
     /// The interrupt priority. Defaults to `4`.
     const INTERRUPT_PRIORITY: InterruptPriority = 4;
 }
 
-const fn min128(x: u128, y: u128) -> u128 {
+/// The divider ratios supported by `CMCR.CKS` and `CMWCR.CKS`, in ascending
+/// order.
+const PREDIVIDERS: [u64; 4] = [8, 32, 128, 512];
+
+/// Choose the predivider based on [`CmtOptions::TARGET_RESOLUTION_NS`] and
+/// [`CmtOptions::TARGET_MAX_INTERVAL_US`] (or their [`CmtwOptions`]
+/// counterparts). `counter_bits` is the width of the timer's counter, which
+/// determines the longest interval it can measure.
+///
+/// [`CmtwOptions`]: crate::CmtwOptions
+pub(crate) const fn choose_predivider(
+    freq_num: u64,
+    freq_denom: u64,
+    resolution_ns: Option<u64>,
+    max_interval_us: Option<u64>,
+    counter_bits: u32,
+) -> u64 {
+    // The length of one hardware timer cycle is
+    // `predivider * freq_denom / freq_num` seconds.
+    const fn meets_resolution(
+        predivider: u64,
+        freq_num: u64,
+        freq_denom: u64,
+        resolution_ns: Option<u64>,
+    ) -> bool {
+        match resolution_ns {
+            Some(ns) => {
+                predivider as u128 * freq_denom as u128 * 1_000_000_000
+                    <= ns as u128 * freq_num as u128
+            }
+            None => true,
+        }
+    }
+
+    const fn meets_max_interval(
+        predivider: u64,
+        freq_num: u64,
+        freq_denom: u64,
+        max_interval_us: Option<u64>,
+        counter_bits: u32,
+    ) -> bool {
+        match max_interval_us {
+            Some(us) => {
+                (1u128 << counter_bits) * predivider as u128 * freq_denom as u128 * 1_000_000
+                    >= us as u128 * freq_num as u128
+            }
+            None => true,
+        }
+    }
+
+    match (resolution_ns, max_interval_us) {
+        (None, None) => panic!(
+            "`PREDIVIDER` must be specified unless `TARGET_RESOLUTION_NS` or \
+            `TARGET_MAX_INTERVAL_US` is specified"
+        ),
+        (Some(_), None) => {
+            // Choose the largest one
+            let mut i = PREDIVIDERS.len();
+            while i > 0 {
+                i -= 1;
+                if meets_resolution(PREDIVIDERS[i], freq_num, freq_denom, resolution_ns) {
+                    return PREDIVIDERS[i];
+                }
+            }
+            panic!(
+                "no `PREDIVIDER` can achieve `TARGET_RESOLUTION_NS`; the \
+                input clock is too slow"
+            )
+        }
+        (_, Some(_)) => {
+            // Choose the smallest one
+            let mut i = 0;
+            while i < PREDIVIDERS.len() {
+                if meets_max_interval(
+                    PREDIVIDERS[i],
+                    freq_num,
+                    freq_denom,
+                    max_interval_us,
+                    counter_bits,
+                ) {
+                    if !meets_resolution(PREDIVIDERS[i], freq_num, freq_denom, resolution_ns) {
+                        panic!(
+                            "no `PREDIVIDER` can achieve both \
+                            `TARGET_RESOLUTION_NS` and `TARGET_MAX_INTERVAL_US`"
+                        );
+                    }
+                    return PREDIVIDERS[i];
+                }
+                i += 1;
+            }
+            panic!(
+                "no `PREDIVIDER` can achieve `TARGET_MAX_INTERVAL_US`; the \
+                input clock is too fast"
+            )
+        }
+    }
+}
+
+pub(crate) const fn min128(x: u128, y: u128) -> u128 {
     if x < y {
         x
     } else {
         y
     }
 }
+
+/// Calculate the value of the simulated 32-bit counter from its last known
+/// value and the current value of the 16-bit hardware counter that it extends.
+/// Shared by the CMT and TMR drivers.
+#[inline]
+#[cfg_attr(not(target_os = "none"), allow(dead_code))]
+pub(crate) const fn current_hw_tick_count32(
+    hw_tick_count32: u32,
+    current_hw_tick_count16: u16,
+) -> u32 {
+    let hw_tick_count16: u16 = hw_tick_count32 as _;
+    hw_tick_count32.wrapping_add(current_hw_tick_count16.wrapping_sub(hw_tick_count16) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FREQ: u64 = 48_000_000;
+
+    #[test]
+    fn predivider_resolution_chooses_largest() {
+        // 1 µs allows up to ÷48
+        assert_eq!(choose_predivider(FREQ, 1, Some(1_000), None, 16), 32);
+        // 20 µs allows up to ÷960
+        assert_eq!(choose_predivider(FREQ, 1, Some(20_000), None, 16), 512);
+        // The frequency denominator lengthens each input cycle
+        assert_eq!(choose_predivider(FREQ, 4, Some(1_000), None, 16), 8);
+    }
+
+    #[test]
+    fn predivider_max_interval_chooses_smallest() {
+        // 10 ms needs ÷7.32 or more with a 16-bit counter
+        assert_eq!(choose_predivider(FREQ, 1, None, Some(10_000), 16), 8);
+        // 100 ms needs ÷73.2 or more with a 16-bit counter
+        assert_eq!(choose_predivider(FREQ, 1, None, Some(100_000), 16), 128);
+    }
+
+    #[test]
+    fn predivider_max_interval_depends_on_counter_width() {
+        assert_eq!(choose_predivider(FREQ, 1, None, Some(1_000_000), 32), 8);
+        assert_eq!(choose_predivider(FREQ, 1, None, Some(100_000), 32), 8);
+    }
+
+    #[test]
+    fn predivider_both() {
+        assert_eq!(
+            choose_predivider(FREQ, 1, Some(5_000), Some(100_000), 16),
+            128
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn predivider_none() {
+        choose_predivider(FREQ, 1, None, None, 16);
+    }
+
+    #[test]
+    #[should_panic]
+    fn predivider_resolution_unachievable() {
+        choose_predivider(FREQ, 1, Some(100), None, 16);
+    }
+
+    #[test]
+    #[should_panic]
+    fn predivider_max_interval_unachievable() {
+        choose_predivider(FREQ, 1, None, Some(1_000_000), 16);
+    }
+
+    #[test]
+    #[should_panic]
+    fn predivider_both_unachievable() {
+        choose_predivider(FREQ, 1, Some(1_000), Some(100_000), 16);
+    }
+
+    #[test]
+    fn hw_tick_count32_extends_counter() {
+        assert_eq!(current_hw_tick_count32(0, 0), 0);
+        assert_eq!(current_hw_tick_count32(0, 100), 100);
+        assert_eq!(current_hw_tick_count32(0x1234_5678, 0x5678), 0x1234_5678);
+        assert_eq!(current_hw_tick_count32(0x1234_5678, 0x6000), 0x1234_6000);
+    }
+
+    #[test]
+    fn hw_tick_count32_hw_counter_wraps() {
+        // The 16-bit counter wrapped around since the last update
+        assert_eq!(current_hw_tick_count32(0x0001_fff0, 0x0010), 0x0002_0010);
+        assert_eq!(current_hw_tick_count32(0x1234_ffff, 0x0000), 0x1235_0000);
+        // Less than one full period of the 16-bit counter has elapsed
+        assert_eq!(current_hw_tick_count32(0x0000_0001, 0x0000), 0x0001_0000);
+    }
+
+    #[test]
+    fn hw_tick_count32_wraps() {
+        assert_eq!(current_hw_tick_count32(0xffff_fff0, 0x0010), 0x0000_0010);
+        assert_eq!(current_hw_tick_count32(0xffff_ffff, 0xffff), 0xffff_ffff);
+    }
+}
@@ -7,11 +7,15 @@ use r3_kernel::{KernelTraits, PortToKernel, System, UTicks};
 use r3_portkit::tickless::{TicklessCfg, TicklessOptions, TicklessStateTrait};
 use rsrx::cmt;
 use tock_registers::{
-    fields::FieldValue,
+    fields::{Field, FieldValue},
     interfaces::{ReadWriteable, Readable, Writeable},
+    RegisterLongName,
 };
 
-use crate::{cmt::cfg::CmtOptions, Icu};
+use crate::{
+    cmt::cfg::{current_hw_tick_count32, CmtOptions},
+    Icu, INTERRUPT_NUM_RANGE,
+};
 
 /// Implemented on a kernel trait type by [`use_cmt!`].
 ///
@@ -36,13 +40,50 @@ pub unsafe trait TimerInstance: KernelTraits + CmtOptions + Icu {
 
     fn timer_state() -> *mut TimerState<Self::TicklessState>;
 
-    const CMT_CONTROL_CKS: FieldValue<u16, cmt::Control::Register> = match Self::PREDIVIDER {
-        8 => cmt::Control::CKS::PclkDividedBy8,
-        32 => cmt::Control::CKS::PclkDividedBy32,
-        128 => cmt::Control::CKS::PclkDividedBy128,
-        512 => cmt::Control::CKS::PclkDividedBy512,
-        _ => unreachable!(),
+    const CMT_CONTROL_CKS: FieldValue<u16, cmt::Control::Register> = control_cks(Self::PREDIVIDER);
+
+    const CMT_START_INTERRUPT: Field<u16, cmt::Start::Register> =
+        start_field(Self::INTERRUPT_CHANNEL);
+
+    const CMT_START_COUNTER: Field<u16, cmt::Start::Register> = start_field(Self::COUNTER_CHANNEL);
+}
+
+/// Get the `CKS` value for the specified predivider. `CKS` is located at
+/// the same position in `CMCR` (CMT) and `CMWCR` (CMTW), which are
+/// distinguished by `R`.
+pub(crate) const fn control_cks<R: RegisterLongName>(predivider: u64) -> FieldValue<u16, R> {
+    let cks = match predivider {
+        8 => 0,
+        32 => 1,
+        128 => 2,
+        512 => 3,
+        _ => panic!("`PREDIVIDER` must be one of `[8, 32, 128, 512]`"),
     };
+    // `CKS` occupies bits 1:0
+    FieldValue::<u16, R>::new(0b11, 0, cks)
+}
+
+/// Get `CMSTR.STRn` for the specified channel.
+pub(crate) const fn start_field(channel: usize) -> Field<u16, cmt::Start::Register> {
+    match channel {
+        0 => cmt::Start::STR0,
+        1 => cmt::Start::STR1,
+        _ => unreachable!(),
+    }
+}
+
+/// Validate [`CmtOptions::INTERRUPT_CHANNEL`] and
+/// [`CmtOptions::INTERRUPT_NUM`].
+pub(crate) const fn validate_interrupt_channel<Traits: CmtOptions>() {
+    assert!(
+        Traits::INTERRUPT_CHANNEL < 2,
+        "`INTERRUPT_CHANNEL` must be `0` or `1`"
+    );
+    assert!(
+        Traits::INTERRUPT_NUM >= INTERRUPT_NUM_RANGE.start
+            && Traits::INTERRUPT_NUM < INTERRUPT_NUM_RANGE.end,
+        "`INTERRUPT_NUM` must be in range `16..256`"
+    );
 }
 
 trait TimerInstanceExt: TimerInstance {
@@ -51,14 +92,26 @@ trait TimerInstanceExt: TimerInstance {
         // Safety: Verified by the user of `use_cmt!`
         unsafe { &*(Self::CMT_BASE as *const cmt::Registers) }
     }
+
+    /// The channel generating timer interrupts
+    #[inline(always)]
+    fn interrupt_channel() -> &'static cmt::channel::Registers {
+        &Self::cmt().channels[Self::INTERRUPT_CHANNEL]
+    }
+
+    /// The channel used as a free-running counter
+    #[inline(always)]
+    fn counter_channel() -> &'static cmt::channel::Registers {
+        &Self::cmt().channels[Self::COUNTER_CHANNEL]
+    }
 }
 impl<T: TimerInstance> TimerInstanceExt for T {}
 
 pub struct TimerState<TicklessState> {
     tickless_state: TicklessState,
     /// The last known value of the simulated 32-bit counter. The current value
-    /// can be calculated using `hw_tick_count32: u32` and `cmt.channels[1].
-    /// cmcnt.get(): u32`, provided that the timer hasn't advanced by more than
+    /// can be calculated using `hw_tick_count32: u32` and the counter
+    /// channel's `cmcnt.get(): u32`, provided that the timer hasn't advanced by more than
     /// 2¹⁶ cycles since the previous update of `hw_tick_count32`.
     hw_tick_count32: u32,
     /// For how many cycles should we wait before `timer_tick` should be called?
@@ -78,10 +131,36 @@ pub const fn configure<C, Traits: TimerInstance>(b: &mut Cfg<C>)
 where
     C: ~const traits::CfgBase<System = System<Traits>> + ~const traits::CfgInterruptLine,
 {
+    validate_interrupt_channel::<Traits>();
+    assert!(
+        Traits::COUNTER_CHANNEL < 2,
+        "`COUNTER_CHANNEL` must be `0` or `1`"
+    );
+    assert!(
+        Traits::COUNTER_CHANNEL != Traits::INTERRUPT_CHANNEL,
+        "`COUNTER_CHANNEL` and `INTERRUPT_CHANNEL` must be different"
+    );
+    assert!(
+        Traits::COUNTER_INTERRUPT_NUM >= INTERRUPT_NUM_RANGE.start
+            && Traits::COUNTER_INTERRUPT_NUM < INTERRUPT_NUM_RANGE.end,
+        "`COUNTER_INTERRUPT_NUM` must be in range `16..256`"
+    );
+    assert!(
+        Traits::COUNTER_INTERRUPT_NUM != Traits::INTERRUPT_NUM,
+        "`COUNTER_INTERRUPT_NUM` and `INTERRUPT_NUM` must be different"
+    );
+
     InterruptLine::define()
         .line(Traits::INTERRUPT_NUM)
         .enabled(true)
         .finish(b);
+
+    // The counter channel never generates interrupts. Reserve the interrupt
+    // line so that the application can't use it.
+    InterruptLine::define()
+        .line(Traits::COUNTER_INTERRUPT_NUM)
+        .enabled(false)
+        .finish(b);
     StaticInterruptHandler::define()
         .line(Traits::INTERRUPT_NUM)
         .start(handle_tick::<Traits>)
@@ -104,32 +183,25 @@ where
 #[inline]
 pub fn init<Traits: TimerInstance>() {
     let cmt = Traits::cmt();
+    let (int_ch, counter_ch) = (Traits::interrupt_channel(), Traits::counter_channel());
 
     // Stop the timers
     cmt.cmstr
-        .write(cmt::Start::STR0::Stop + cmt::Start::STR1::Stop);
+        .write(Traits::CMT_START_INTERRUPT.val(0) + Traits::CMT_START_COUNTER.val(0));
 
-    // Unit 0: Variable interval, interrupts enabled
-    // Unit 1: Free-running (period = 2¹⁶), interrupts disabled
-    cmt.channels[0]
+    // Interrupt channel: Variable interval, interrupts enabled
+    // Counter channel: Free-running (period = 2¹⁶), interrupts disabled
+    int_ch
         .cmcr
         .write(Traits::CMT_CONTROL_CKS + cmt::Control::CMIE::SET);
-    cmt.channels[1].cmcr.write(Traits::CMT_CONTROL_CKS);
-    cmt.channels[0].cmcnt.set(0);
-    cmt.channels[1].cmcnt.set(0);
-    cmt.channels[1].cmcor.set(u16::MAX);
+    counter_ch.cmcr.write(Traits::CMT_CONTROL_CKS);
+    int_ch.cmcnt.set(0);
+    counter_ch.cmcnt.set(0);
+    counter_ch.cmcor.set(u16::MAX);
 
     // Start the timers
     cmt.cmstr
-        .write(cmt::Start::STR0::Start + cmt::Start::STR1::Start);
-}
-
-/// Calculate the value of the simulated 32-bit counter based on the current
-/// CMT state.
-#[inline]
-fn current_hw_tick_count32(hw_tick_count32: u32, current_hw_tick_count16: u16) -> u32 {
-    let hw_tick_count16: u16 = hw_tick_count32 as _;
-    hw_tick_count32.wrapping_add(current_hw_tick_count16.wrapping_sub(hw_tick_count16) as u32)
+        .write(Traits::CMT_START_INTERRUPT.val(1) + Traits::CMT_START_COUNTER.val(1));
 }
 
 /// Implements [`r3_kernel::PortTimer::tick_count`]
@@ -142,9 +214,10 @@ pub unsafe fn tick_count<Traits: TimerInstance>() -> UTicks {
     let tstate = unsafe { &mut *Traits::timer_state() };
 
     // Calculate the value of the simulated 32-bit counter
-    let cmt = Traits::cmt();
-    let cur_hw_tick_count32 =
-        current_hw_tick_count32(tstate.hw_tick_count32, cmt.channels[1].cmcnt.get());
+    let cur_hw_tick_count32 = current_hw_tick_count32(
+        tstate.hw_tick_count32,
+        Traits::counter_channel().cmcnt.get(),
+    );
 
     let tcfg = &Traits::TICKLESS_CFG;
     tstate.tickless_state.tick_count(tcfg, cur_hw_tick_count32)
@@ -162,11 +235,11 @@ pub unsafe fn pend_tick<Traits: TimerInstance>() {
     // Do `timer_tick` on the next interrupt
     tstate.hw_tick_remaining = 0;
 
-    // Configure unit 0 to generate an interrupt on the next cycle.
-    // (We can't make this happen immediately, unfortunately.)
-    let cmt = Traits::cmt();
-    cmt.channels[0].cmcor.set(0);
-    cmt.channels[0].cmcnt.set(0);
+    // Configure the interrupt channel to generate an interrupt on the next
+    // cycle. (We can't make this happen immediately, unfortunately.)
+    let int_ch = Traits::interrupt_channel();
+    int_ch.cmcor.set(0);
+    int_ch.cmcnt.set(0);
 }
 
 /// Implements [`r3_kernel::PortTimer::pend_tick_after`]
@@ -179,9 +252,10 @@ pub unsafe fn pend_tick_after<Traits: TimerInstance>(tick_count_delta: UTicks) {
     let tstate = unsafe { &mut *Traits::timer_state() };
 
     // Update the reference time of the simulated 32-bit counter
-    let cmt = Traits::cmt();
-    let cur_hw_tick_count32 =
-        current_hw_tick_count32(tstate.hw_tick_count32, cmt.channels[1].cmcnt.get());
+    let cur_hw_tick_count32 = current_hw_tick_count32(
+        tstate.hw_tick_count32,
+        Traits::counter_channel().cmcnt.get(),
+    );
     tstate.hw_tick_count32 = cur_hw_tick_count32;
 
     let tcfg = &Traits::TICKLESS_CFG;
@@ -199,14 +273,16 @@ pub unsafe fn pend_tick_after<Traits: TimerInstance>(tick_count_delta: UTicks) {
     // track of it.
     let max_cmcor = u16::MAX - <Traits as CmtOptions>::HEADROOM;
 
-    // Schedule unit 0
-    cmt.cmstr.modify(cmt::Start::STR1::Stop);
+    // Schedule the interrupt channel
+    let cmt = Traits::cmt();
+    let int_ch = Traits::interrupt_channel();
+    cmt.cmstr.modify(Traits::CMT_START_INTERRUPT.val(0));
     let _ = InterruptLine::<System<Traits>>::from_num(Traits::INTERRUPT_NUM).clear();
-    cmt.channels[0]
+    int_ch
         .cmcor
         .set(hw_ticks.saturating_sub(1).min(max_cmcor as u32) as u16);
-    cmt.channels[0].cmcnt.set(0);
-    cmt.cmstr.modify(cmt::Start::STR1::Start);
+    int_ch.cmcnt.set(0);
+    cmt.cmstr.modify(Traits::CMT_START_INTERRUPT.val(1));
 }
 
 #[inline]
@@ -215,9 +291,10 @@ fn handle_tick<Traits: TimerInstance>() {
     let tstate = unsafe { &mut *Traits::timer_state() };
 
     // Update the reference time of the simulated 32-bit counter
-    let cmt = Traits::cmt();
-    let cur_hw_tick_count32 =
-        current_hw_tick_count32(tstate.hw_tick_count32, cmt.channels[1].cmcnt.get());
+    let cur_hw_tick_count32 = current_hw_tick_count32(
+        tstate.hw_tick_count32,
+        Traits::counter_channel().cmcnt.get(),
+    );
     tstate.hw_tick_remaining = tstate
         .hw_tick_remaining
         .saturating_sub(cur_hw_tick_count32.wrapping_sub(tstate.hw_tick_count32));
@@ -0,0 +1,122 @@
+//! The public interface for the tickful Compare Match Timer (CMT) timer driver.
+
+/// Attach the implementation of [`PortTimer`] based on Compare Match Timer
+/// (CMT) to a given kernel trait type. This macro also implements [`Timer`] on
+/// the kernel trait type.
+/// **Requires [`CmtOptions`].**
+///
+/// [`PortTimer`]: r3_kernel::PortTimer
+/// [`Timer`]: crate::Timer
+/// [`CmtOptions`]: crate::CmtOptions
+///
+/// Unlike [`use_cmt!`], this driver uses only one channel of the CMT instance
+/// ([`CmtOptions::INTERRUPT_CHANNEL`]), which generates an interrupt at a fixed
+/// interval ([`CmtOptions::TICK_PERIOD`]). The other channel is left for the
+/// application to use.
+///
+/// [`use_cmt!`]: crate::use_cmt
+/// [`CmtOptions::INTERRUPT_CHANNEL`]: crate::CmtOptions::INTERRUPT_CHANNEL
+/// [`CmtOptions::TICK_PERIOD`]: crate::CmtOptions::TICK_PERIOD
+///
+/// You should do the following:
+///
+///  - Implement [`CmtOptions`] on the kernel trait type `$Traits`.
+///  - Call `$Traits::configure_timer()` in your configuration function.
+///    See the following example.
+///
+/// ```rust,ignore
+/// r3_port_rx::use_cmt_periodic!(unsafe impl PortTimer for SystemTraits);
+///
+/// impl r3_port_rx::CmtOptions for SystemTraits {
+///     const FREQUENCY: u64 = 48_000_000;
+///     const PREDIVIDER: u64 = 8;
+///     // 1 millisecond
+///     const TICK_PERIOD: u32 = 6_000;
+/// }
+///
+/// const fn configure_app(b: &mut r3_kernel::Cfg<SystemTraits>) -> Objects {
+///     SystemTraits::configure_timer(b);
+///     /* ... */
+/// }
+/// ```
+///
+/// CMT can't raise a timer interrupt on demand, so [`PortTimer::pend_tick`]
+/// pends the timer interrupt line by software (see
+/// [the crate-level documentation](crate#interrupts)). This requires `SWINT`
+/// to stay enabled with a managed priority (see
+/// [`ThreadingOptions::SWINT_PRIORITY`]) and not to be
+/// [`ThreadingOptions::FAST_INTERRUPT_NUM`]. `pend_tick` panics otherwise.
+///
+/// [`ThreadingOptions::SWINT_PRIORITY`]: crate::ThreadingOptions::SWINT_PRIORITY
+/// [`ThreadingOptions::FAST_INTERRUPT_NUM`]: crate::ThreadingOptions::FAST_INTERRUPT_NUM
+///
+/// [`PortTimer::pend_tick`]: r3_kernel::PortTimer::pend_tick
+///
+/// # Safety
+///
+///  - `CmtOptions` must be configured correctly.
+///
+#[macro_export]
+macro_rules! use_cmt_periodic {
+    (unsafe impl PortTimer for $Traits:ty) => {
+        const _: () = {
+            use $crate::r3_core::{
+                kernel::{traits, Cfg},
+                utils::Init,
+            };
+            use $crate::r3_kernel::{PortTimer, System, UTicks};
+            use $crate::r3_portkit::tickful;
+            use $crate::{cmt_periodic, CmtOptions, Timer};
+
+            impl PortTimer for $Traits {
+                const MAX_TICK_COUNT: UTicks = u32::MAX;
+                const MAX_TIMEOUT: UTicks =
+                    <$Traits as cmt_periodic::imp::PeriodicTimerInstance>::TICKFUL_CFG
+                        .max_timeout();
+
+                unsafe fn tick_count() -> UTicks {
+                    // Safety: We are just forwarding the call
+                    unsafe { cmt_periodic::imp::tick_count::<Self>() }
+                }
+
+                unsafe fn pend_tick() {
+                    // Safety: We are just forwarding the call
+                    unsafe { cmt_periodic::imp::pend_tick::<Self>() }
+                }
+
+                unsafe fn pend_tick_after(_tick_count_delta: UTicks) {
+                    // The timer interrupt is taken periodically regardless
+                }
+            }
+
+            impl Timer for $Traits {
+                unsafe fn init() {
+                    cmt_periodic::imp::init::<Self>()
+                }
+            }
+
+            static mut TIMER_STATE: cmt_periodic::imp::TimerState<
+                <$Traits as cmt_periodic::imp::PeriodicTimerInstance>::TickfulState,
+            > = Init::INIT;
+
+            // Safety: Only `use_cmt_periodic!` is allowed to `impl` this
+            unsafe impl cmt_periodic::imp::PeriodicTimerInstance for $Traits {
+                type TickfulState = tickful::TickfulState<{ Self::TICKFUL_CFG }>;
+
+                fn timer_state() -> *mut cmt_periodic::imp::TimerState<Self::TickfulState> {
+                    unsafe { core::ptr::addr_of_mut!(TIMER_STATE) }
+                }
+            }
+
+            impl $Traits {
+                pub const fn configure_timer<C>(b: &mut Cfg<C>)
+                where
+                    C: ~const traits::CfgBase<System = System<Self>>
+                        + ~const traits::CfgInterruptLine,
+                {
+                    cmt_periodic::imp::configure(b);
+                }
+            }
+        };
+    };
+}
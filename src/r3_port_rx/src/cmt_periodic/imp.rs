@@ -0,0 +1,192 @@
+//! The implementation of the tickful timer driver based on Compare Match Timer
+//! (CMT).
+use r3_core::{
+    kernel::{traits, Cfg, InterruptLine, StartupHook, StaticInterruptHandler},
+    utils::Init,
+};
+use r3_kernel::{KernelTraits, PortToKernel, System, UTicks};
+use r3_portkit::tickful::{TickfulCfg, TickfulOptions, TickfulStateTrait};
+use rsrx::cmt;
+use tock_registers::{
+    fields::{Field, FieldValue},
+    interfaces::{ReadWriteable, Writeable},
+};
+
+use crate::{
+    cmt::cfg::CmtOptions,
+    cmt::imp::{control_cks, start_field, validate_interrupt_channel},
+    Icu,
+};
+
+/// Implemented on a kernel trait type by [`use_cmt_periodic!`].
+///
+/// # Safety
+///
+/// Only meant to be implemented by [`use_cmt_periodic!`].
+pub unsafe trait PeriodicTimerInstance: KernelTraits + CmtOptions + Icu {
+    const TICKFUL_CFG: TickfulCfg = {
+        assert!(
+            <Self as CmtOptions>::TICK_PERIOD >= 1 && <Self as CmtOptions>::TICK_PERIOD <= 0x10000,
+            "`TICK_PERIOD` must be in range `1..=0x10000`"
+        );
+        match TickfulCfg::new(TickfulOptions {
+            hw_freq_num: <Self as CmtOptions>::FREQUENCY,
+            hw_freq_denom: <Self as CmtOptions>::FREQUENCY_DENOMINATOR
+                .checked_mul(<Self as CmtOptions>::PREDIVIDER)
+                .expect("frequency denominator overflowed"),
+            hw_tick_period: <Self as CmtOptions>::TICK_PERIOD,
+        }) {
+            Ok(x) => x,
+            Err(e) => e.panic(),
+        }
+    };
+
+    type TickfulState: TickfulStateTrait;
+
+    fn timer_state() -> *mut TimerState<Self::TickfulState>;
+
+    const CMT_CONTROL_CKS: FieldValue<u16, cmt::Control::Register> = control_cks(Self::PREDIVIDER);
+
+    const CMT_START_INTERRUPT: Field<u16, cmt::Start::Register> =
+        start_field(Self::INTERRUPT_CHANNEL);
+}
+
+trait PeriodicTimerInstanceExt: PeriodicTimerInstance {
+    #[inline(always)]
+    fn cmt() -> &'static cmt::Registers {
+        // Safety: Verified by the user of `use_cmt_periodic!`
+        unsafe { &*(Self::CMT_BASE as *const cmt::Registers) }
+    }
+
+    /// The channel generating timer interrupts
+    #[inline(always)]
+    fn interrupt_channel() -> &'static cmt::channel::Registers {
+        &Self::cmt().channels[Self::INTERRUPT_CHANNEL]
+    }
+}
+impl<T: PeriodicTimerInstance> PeriodicTimerInstanceExt for T {}
+
+pub struct TimerState<TickfulState> {
+    tickful_state: TickfulState,
+    /// `true` if the interrupt line was pended by [`pend_tick`] and the
+    /// resulting interrupt hasn't been handled yet. The interrupt taken for
+    /// this request must not advance the tick count.
+    tick_pended: bool,
+}
+
+impl<TickfulState: Init> Init for TimerState<TickfulState> {
+    const INIT: Self = Self {
+        tickful_state: Init::INIT,
+        tick_pended: false,
+    };
+}
+
+/// The configuration function.
+pub const fn configure<C, Traits: PeriodicTimerInstance>(b: &mut Cfg<C>)
+where
+    C: ~const traits::CfgBase<System = System<Traits>> + ~const traits::CfgInterruptLine,
+{
+    validate_interrupt_channel::<Traits>();
+
+    InterruptLine::define()
+        .line(Traits::INTERRUPT_NUM)
+        .enabled(true)
+        .finish(b);
+    StaticInterruptHandler::define()
+        .line(Traits::INTERRUPT_NUM)
+        .start(handle_tick::<Traits>)
+        .finish(b);
+
+    if <Traits as CmtOptions>::IPR_INDEX.is_some() {
+        StartupHook::define()
+            .start(|| {
+                Traits::set_interrupt_group_priority(
+                    <Traits as CmtOptions>::IPR_INDEX.unwrap(),
+                    <Traits as CmtOptions>::INTERRUPT_PRIORITY,
+                )
+                .unwrap()
+            })
+            .finish(b);
+    }
+}
+
+/// Implements [`crate::Timer::init`]
+#[inline]
+pub fn init<Traits: PeriodicTimerInstance>() {
+    let cmt = Traits::cmt();
+    let int_ch = Traits::interrupt_channel();
+
+    // Stop the timer. Leave the other channel of the unit untouched; it's
+    // available to the application.
+    cmt.cmstr.modify(Traits::CMT_START_INTERRUPT.val(0));
+
+    // Interrupt channel: Fixed interval, interrupts enabled
+    int_ch
+        .cmcr
+        .write(Traits::CMT_CONTROL_CKS + cmt::Control::CMIE::SET);
+    int_ch.cmcnt.set(0);
+    int_ch
+        .cmcor
+        .set((<Traits as CmtOptions>::TICK_PERIOD - 1) as u16);
+
+    // Start the timer
+    cmt.cmstr.modify(Traits::CMT_START_INTERRUPT.val(1));
+}
+
+/// Implements [`r3_kernel::PortTimer::tick_count`]
+///
+/// # Safety
+///
+/// Only meant to be referenced by `use_cmt_periodic!`.
+pub unsafe fn tick_count<Traits: PeriodicTimerInstance>() -> UTicks {
+    // Safety: CPU Lock protects it from concurrent access
+    let tstate = unsafe { &*Traits::timer_state() };
+
+    tstate.tickful_state.tick_count()
+}
+
+/// Implements [`r3_kernel::PortTimer::pend_tick`]
+///
+/// # Safety
+///
+/// Only meant to be referenced by `use_cmt_periodic!`.
+pub unsafe fn pend_tick<Traits: PeriodicTimerInstance>() {
+    // Safety: CPU Lock protects it from concurrent access
+    let tstate = unsafe { &mut *Traits::timer_state() };
+
+    if tstate.tick_pended {
+        // The previous request hasn't been handled yet
+        return;
+    }
+
+    // CMT can't raise a compare match interrupt on demand, and forcing an
+    // early compare match would shorten the current tick period. Pend the
+    // interrupt line by software instead (see the crate-level documentation)
+    // and remember not to count the resulting interrupt as a tick. The
+    // request is delivered exactly once, even if the interrupt line is
+    // disabled in the meantime, so `handle_tick` will consume the flag.
+    InterruptLine::<System<Traits>>::from_num(Traits::INTERRUPT_NUM)
+        .pend()
+        .expect("failed to pend the timer interrupt; `SWINT` must be enabled");
+    tstate.tick_pended = true;
+}
+
+#[inline]
+fn handle_tick<Traits: PeriodicTimerInstance>() {
+    // Safety: CPU Lock protects it from concurrent access
+    let tstate = unsafe { &mut *Traits::timer_state() };
+
+    // We can't tell a compare match from a software-pended interrupt, but
+    // if both are pending, they are taken as two separate interrupts. Skip
+    // exactly one of them, so that the tick count advances only once per
+    // tick period either way.
+    if tstate.tick_pended {
+        tstate.tick_pended = false;
+    } else {
+        let tcfg = &Traits::TICKFUL_CFG;
+        tstate.tickful_state.tick(tcfg);
+    }
+
+    // Safety: CPU Lock inactive, an interrupt context
+    unsafe { Traits::timer_tick() };
+}
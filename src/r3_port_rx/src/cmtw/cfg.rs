@@ -0,0 +1,196 @@
+//! The public interface for the Compare Match Timer W (CMTW) timer driver.
+use r3_core::kernel::{InterruptNum, InterruptPriority};
+
+use crate::cmt::cfg::{choose_predivider, min128};
+
+/// Attach the implementation of [`PortTimer`] based on Compare Match Timer W
+/// (CMTW) to a given kernel trait type. This macro also implements [`Timer`]
+/// on the kernel trait type.
+/// **Requires [`CmtwOptions`].**
+///
+/// [`PortTimer`]: r3_kernel::PortTimer
+/// [`Timer`]: crate::Timer
+///
+/// This driver runs the 32-bit counter of a CMTW unit freely and uses the
+/// output compare register 0 (`CMWOCR0`) to generate interrupts only when the
+/// kernel needs one. Unlike [`use_cmt!`], it doesn't have to wake up the
+/// processor every 2¹⁶ cycles to keep track of time.
+///
+/// [`use_cmt!`]: crate::use_cmt
+///
+/// You should do the following:
+///
+///  - Implement [`CmtwOptions`] on the kernel trait type `$Traits`.
+///  - Assign the output compare 0 interrupt source (`OC0Im`) of the CMTW unit
+///    to [`CmtwOptions::INTERRUPT_NUM`] by
+///    [`ThreadingOptions::INTERRUPT_SOURCE_SELECTIONS`].
+///  - Call `$Traits::configure_timer()` in your configuration function.
+///    See the following example.
+///
+/// [`ThreadingOptions::INTERRUPT_SOURCE_SELECTIONS`]: crate::ThreadingOptions::INTERRUPT_SOURCE_SELECTIONS
+///
+/// ```rust,ignore
+/// r3_port_rx::use_cmtw!(unsafe impl PortTimer for SystemTraits);
+///
+/// impl r3_port_rx::ThreadingOptions for SystemTraits {
+///     const ICU_VARIANT: r3_port_rx::IcuVariant = r3_port_rx::IcuVariant::Icud;
+///     // `OC0I0_SOURCE` is the interrupt source number of CMTW0's `OC0I0`,
+///     // which can be found in the device's hardware manual
+///     const INTERRUPT_SOURCE_SELECTIONS: &'static [r3_port_rx::InterruptSourceSelection] =
+///         &[r3_port_rx::InterruptSourceSelection { num: 144, source: OC0I0_SOURCE }];
+/// }
+///
+/// impl r3_port_rx::CmtwOptions for SystemTraits {
+///     const FREQUENCY: u64 = 60_000_000;
+///     // 1 microsecond or finer
+///     const TARGET_RESOLUTION_NS: Option<u64> = Some(1_000);
+///     const INTERRUPT_NUM: InterruptNum = 144;
+/// }
+///
+/// const fn configure_app(b: &mut r3_kernel::Cfg<SystemTraits>) -> Objects {
+///     SystemTraits::configure_timer(b);
+///     /* ... */
+/// }
+/// ```
+///
+/// # Safety
+///
+///  - `CmtwOptions` must be configured correctly.
+///
+#[macro_export]
+macro_rules! use_cmtw {
+    (unsafe impl PortTimer for $Traits:ty) => {
+        const _: () = {
+            use $crate::r3_core::{
+                kernel::{traits, Cfg},
+                utils::Init,
+            };
+            use $crate::r3_kernel::{PortTimer, System, UTicks};
+            use $crate::r3_portkit::tickless;
+            use $crate::{cmtw, CmtwOptions, Timer};
+
+            impl PortTimer for $Traits {
+                const MAX_TICK_COUNT: UTicks =
+                    <$Traits as cmtw::imp::TimerInstance>::TICKLESS_CFG.max_tick_count();
+                const MAX_TIMEOUT: UTicks =
+                    <$Traits as cmtw::imp::TimerInstance>::TICKLESS_CFG.max_timeout();
+
+                unsafe fn tick_count() -> UTicks {
+                    // Safety: We are just forwarding the call
+                    unsafe { cmtw::imp::tick_count::<Self>() }
+                }
+
+                unsafe fn pend_tick() {
+                    // Safety: We are just forwarding the call
+                    unsafe { cmtw::imp::pend_tick::<Self>() }
+                }
+
+                unsafe fn pend_tick_after(tick_count_delta: UTicks) {
+                    // Safety: We are just forwarding the call
+                    unsafe { cmtw::imp::pend_tick_after::<Self>(tick_count_delta) }
+                }
+            }
+
+            impl Timer for $Traits {
+                unsafe fn init() {
+                    unsafe { cmtw::imp::init::<Self>() }
+                }
+            }
+
+            static mut TIMER_STATE: <$Traits as cmtw::imp::TimerInstance>::TicklessState =
+                Init::INIT;
+
+            // Safety: Only `use_cmtw!` is allowed to `impl` this
+            unsafe impl cmtw::imp::TimerInstance for $Traits {
+                type TicklessState = tickless::TicklessState<{ Self::TICKLESS_CFG }>;
+
+                fn tickless_state() -> *mut Self::TicklessState {
+                    unsafe { core::ptr::addr_of_mut!(TIMER_STATE) }
+                }
+            }
+
+            impl $Traits {
+                pub const fn configure_timer<C>(b: &mut Cfg<C>)
+                where
+                    C: ~const traits::CfgBase<System = System<Self>>
+                        + ~const traits::CfgInterruptLine,
+                {
+                    cmtw::imp::configure(b);
+                }
+            }
+        };
+    };
+}
+
+/// The options for [`use_cmtw!`].
+pub trait CmtwOptions {
+    /// The base address of the memory-mapped registers exposed by a Compare
+    /// Match Timer W unit. Defaults to `0x0009_4200` (CMTW0) when unspecified.
+    /// Use `0x0009_4280` for CMTW1.
+    const CMTW_BASE: *mut () = 0x0009_4200 as _;
+
+    /// The numerator of the effective input clock rate (usually PCLK) of the
+    /// timer unit. This will be further divided by [`Self::PREDIVIDER`] to
+    /// determine the actual timer clock.
+    const FREQUENCY: u64;
+
+    /// The denominator of the effective input clock rate (usually PCLK) of the
+    /// timer unit. Defaults to `1`.
+    const FREQUENCY_DENOMINATOR: u64 = 1;
+
+    /// Set the divider ratio for the predivider (`CMWCR.CKS`). Must be one of
+    /// `[8, 32, 128, 512]`.
+    ///
+    /// Defaults to the value chosen based on [`Self::TARGET_RESOLUTION_NS`] and
+    /// [`Self::TARGET_MAX_INTERVAL_US`]. At least one of them must be specified
+    /// if this option is not specified.
+    const PREDIVIDER: u64 = choose_predivider(
+        Self::FREQUENCY,
+        Self::FREQUENCY_DENOMINATOR,
+        Self::TARGET_RESOLUTION_NS,
+        Self::TARGET_MAX_INTERVAL_US,
+        32,
+    );
+
+    /// The desired maximum length of one hardware timer cycle, measured in
+    /// nanoseconds. Used to choose the default value of [`Self::PREDIVIDER`].
+    ///
+    /// If this is the only one specified, the largest divider ratio meeting
+    /// this requirement is chosen. Defaults to `None`.
+    const TARGET_RESOLUTION_NS: Option<u64> = None;
+
+    /// The desired minimum length of the longest interval the CMTW counter
+    /// can measure (2³² hardware timer cycles), measured in microseconds. Used
+    /// to choose the default value of [`Self::PREDIVIDER`].
+    ///
+    /// The smallest divider ratio (which maximizes the resolution) meeting
+    /// this requirement and [`Self::TARGET_RESOLUTION_NS`] is chosen. Defaults
+    /// to `None`.
+    const TARGET_MAX_INTERVAL_US: Option<u64> = None;
+
+    /// The maximum permissible timer interrupt latency, measured in hardware
+    /// timer cycles.
+    ///
+    /// Defaults to `min(FREQUENCY / FREQUENCY_DENOMINATOR / PREDIVIDER / 100,
+    /// 0x4000_0000)` (10 milliseconds maximum), which follows
+    /// [`Self::PREDIVIDER`] even if it's chosen automatically.
+    const HEADROOM: u32 = min128(
+        Self::FREQUENCY as u128
+            / Self::FREQUENCY_DENOMINATOR as u128
+            / Self::PREDIVIDER as u128
+            / 100,
+        0x4000_0000,
+    ) as u32;
+
+    /// The interrupt number to which the output compare 0 interrupt source
+    /// (`OC0Im`) of the CMTW unit is assigned.
+    const INTERRUPT_NUM: InterruptNum;
+
+    /// The IPR register used to set the priority of [`Self::INTERRUPT_NUM`].
+    /// Defaults to `Some(INTERRUPT_NUM)`, which is correct for the
+    /// software-configurable interrupt vectors of ICUd.
+    const IPR_INDEX: Option<usize> = Some(Self::INTERRUPT_NUM);
+
+    /// The interrupt priority. Defaults to `4`.
+    const INTERRUPT_PRIORITY: InterruptPriority = 4;
+}
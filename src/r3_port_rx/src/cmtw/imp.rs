@@ -0,0 +1,190 @@
+//! The implementation of the timer driver based on Compare Match Timer W
+//! (CMTW).
+use r3_core::kernel::{traits, Cfg, InterruptLine, StartupHook, StaticInterruptHandler};
+use r3_kernel::{KernelTraits, PortToKernel, System, UTicks};
+use r3_portkit::tickless::{TicklessCfg, TicklessOptions, TicklessStateTrait};
+use rsrx::cmtw;
+use tock_registers::{
+    fields::FieldValue,
+    interfaces::{Readable, Writeable},
+};
+
+use crate::{cmt::imp::control_cks, cmtw::cfg::CmtwOptions, Icu, INTERRUPT_NUM_RANGE};
+
+/// Implemented on a kernel trait type by [`use_cmtw!`].
+///
+/// # Safety
+///
+/// Only meant to be implemented by [`use_cmtw!`].
+pub unsafe trait TimerInstance: KernelTraits + CmtwOptions + Icu {
+    const TICKLESS_CFG: TicklessCfg = match TicklessCfg::new(TicklessOptions {
+        hw_freq_num: <Self as CmtwOptions>::FREQUENCY,
+        hw_freq_denom: <Self as CmtwOptions>::FREQUENCY_DENOMINATOR
+            .checked_mul(<Self as CmtwOptions>::PREDIVIDER)
+            .expect("frequency denominator overflowed"),
+        hw_headroom_ticks: <Self as CmtwOptions>::HEADROOM,
+        force_full_hw_period: true,
+        resettable: false,
+    }) {
+        Ok(x) => x,
+        Err(e) => e.panic(),
+    };
+
+    type TicklessState: TicklessStateTrait;
+
+    fn tickless_state() -> *mut Self::TicklessState;
+
+    const CMTW_CONTROL_CKS: FieldValue<u16, cmtw::Control::Register> =
+        control_cks(<Self as CmtwOptions>::PREDIVIDER);
+}
+
+trait TimerInstanceExt: TimerInstance {
+    #[inline(always)]
+    fn cmtw() -> &'static cmtw::Registers {
+        // Safety: Verified by the user of `use_cmtw!`
+        unsafe { &*(Self::CMTW_BASE as *const cmtw::Registers) }
+    }
+}
+impl<T: TimerInstance> TimerInstanceExt for T {}
+
+/// The configuration function.
+pub const fn configure<C, Traits: TimerInstance>(b: &mut Cfg<C>)
+where
+    C: ~const traits::CfgBase<System = System<Traits>> + ~const traits::CfgInterruptLine,
+{
+    assert!(
+        Traits::INTERRUPT_NUM >= INTERRUPT_NUM_RANGE.start
+            && Traits::INTERRUPT_NUM < INTERRUPT_NUM_RANGE.end,
+        "`INTERRUPT_NUM` must be in range `16..256`"
+    );
+
+    InterruptLine::define()
+        .line(Traits::INTERRUPT_NUM)
+        .enabled(true)
+        .finish(b);
+    StaticInterruptHandler::define()
+        .line(Traits::INTERRUPT_NUM)
+        .start(handle_tick::<Traits>)
+        .finish(b);
+
+    if <Traits as CmtwOptions>::IPR_INDEX.is_some() {
+        StartupHook::define()
+            .start(|| {
+                Traits::set_interrupt_group_priority(
+                    <Traits as CmtwOptions>::IPR_INDEX.unwrap(),
+                    <Traits as CmtwOptions>::INTERRUPT_PRIORITY,
+                )
+                .unwrap()
+            })
+            .finish(b);
+    }
+}
+
+/// Implements [`crate::Timer::init`]
+#[inline]
+pub fn init<Traits: TimerInstance>() {
+    let cmtw = Traits::cmtw();
+
+    // Stop the timer
+    cmtw.cmwstr.write(cmtw::Start::STR::Stop);
+
+    // 32-bit free-running counter. `CMWOCR0` generates interrupts.
+    cmtw.cmwcr.write(
+        Traits::CMTW_CONTROL_CKS
+            + cmtw::Control::OC0IE::SET
+            + cmtw::Control::CMS::Bits32
+            + cmtw::Control::CCLR::FreeRunning,
+    );
+    cmtw.cmwior
+        .write(cmtw::IoControl::OC0::Retain + cmtw::IoControl::OC0E::SET);
+    // The counter starts at zero, which `TicklessState::INIT` assumes
+    cmtw.cmwcnt.set(0);
+    cmtw.cmwocr0.set(u32::MAX);
+
+    // Start the timer
+    cmtw.cmwstr.write(cmtw::Start::STR::Start);
+}
+
+/// Implements [`r3_kernel::PortTimer::tick_count`]
+///
+/// # Safety
+///
+/// Only meant to be referenced by `use_cmtw!`.
+pub unsafe fn tick_count<Traits: TimerInstance>() -> UTicks {
+    // Safety: CPU Lock protects it from concurrent access
+    let tstate = unsafe { &*Traits::tickless_state() };
+
+    let tcfg = &Traits::TICKLESS_CFG;
+    tstate.tick_count(tcfg, Traits::cmtw().cmwcnt.get())
+}
+
+/// Implements [`r3_kernel::PortTimer::pend_tick`]
+///
+/// # Safety
+///
+/// Only meant to be referenced by `use_cmtw!`.
+pub unsafe fn pend_tick<Traits: TimerInstance>() {
+    // Generate an interrupt as soon as possible
+    set_compare::<Traits>(Traits::cmtw().cmwcnt.get(), 1);
+}
+
+/// Implements [`r3_kernel::PortTimer::pend_tick_after`]
+///
+/// # Safety
+///
+/// Only meant to be referenced by `use_cmtw!`.
+pub unsafe fn pend_tick_after<Traits: TimerInstance>(tick_count_delta: UTicks) {
+    // Safety: CPU Lock protects it from concurrent access
+    let tstate = unsafe { &mut *Traits::tickless_state() };
+
+    let tcfg = &Traits::TICKLESS_CFG;
+    let cur_hw_tick_count = Traits::cmtw().cmwcnt.get();
+    let hw_ticks = tstate
+        .mark_reference_and_measure(tcfg, cur_hw_tick_count, tick_count_delta)
+        .hw_ticks;
+
+    set_compare::<Traits>(cur_hw_tick_count, hw_ticks.max(1));
+}
+
+/// Configure `CMWOCR0` to generate an interrupt when the counter reaches
+/// `start + hw_ticks`.
+///
+/// A compare match only happens when the counter equals `CMWOCR0`, so if the
+/// counter passes the new value while we are updating it, no interrupt will be
+/// generated until the counter wraps around. Detect this case and retry with a
+/// later deadline.
+fn set_compare<Traits: TimerInstance>(mut start: u32, mut hw_ticks: u32) {
+    let cmtw = Traits::cmtw();
+    let _ = InterruptLine::<System<Traits>>::from_num(Traits::INTERRUPT_NUM).clear();
+
+    let mut margin = 2;
+    loop {
+        let target = start.wrapping_add(hw_ticks);
+        cmtw.cmwocr0.set(target);
+
+        let now = cmtw.cmwcnt.get();
+        if now.wrapping_sub(start) < hw_ticks {
+            break;
+        }
+
+        // Missed the deadline. Since the interrupt handler re-evaluates the
+        // current time, firing late by a few cycles is harmless. Increase the
+        // margin each time so that this loop terminates even if the timer
+        // clock is fast relative to the processor.
+        start = now;
+        hw_ticks = margin;
+        margin = margin.saturating_mul(2);
+    }
+}
+
+#[inline]
+fn handle_tick<Traits: TimerInstance>() {
+    // Safety: CPU Lock protects it from concurrent access
+    let tstate = unsafe { &mut *Traits::tickless_state() };
+
+    let tcfg = &Traits::TICKLESS_CFG;
+    tstate.mark_reference(tcfg, Traits::cmtw().cmwcnt.get());
+
+    // Safety: CPU Lock inactive, an interrupt context
+    unsafe { Traits::timer_tick() };
+}
@@ -0,0 +1,153 @@
+//! The public interface for external interrupt pins (`IRQn`).
+use r3_core::kernel::{InterruptNum, InterruptPriority};
+
+/// The interrupt number of `IRQ0`. `IRQn` uses `IRQ0_INTERRUPT_NUM + n`.
+pub const IRQ0_INTERRUPT_NUM: InterruptNum = 64;
+
+/// The number of external interrupt pins (`IRQ0`–`IRQ15`).
+pub const NUM_IRQ_PINS: usize = 16;
+
+/// The detection sense of an external interrupt pin (`IRQCRn.IRQMD`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum IrqSense {
+    LowLevel = 0b00,
+    FallingEdge = 0b01,
+    RisingEdge = 0b10,
+    BothEdges = 0b11,
+}
+
+/// The sampling clock of the digital filter of an external interrupt pin
+/// (`IRQFLTCn.FCLKSELi`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum IrqFilter {
+    PclkDiv1 = 0b00,
+    PclkDiv8 = 0b01,
+    PclkDiv32 = 0b10,
+    PclkDiv64 = 0b11,
+}
+
+/// Declares an external interrupt pin (`IRQn`) in a configuration function.
+///
+/// This defines an interrupt line and optionally an interrupt handler for
+/// `IRQn`, and configures the pin's detection sense, digital filter, and pin
+/// function at boot time.
+///
+/// ```rust,ignore
+/// const fn configure_app(b: &mut r3_kernel::Cfg<SystemTraits>) -> Objects {
+///     r3_port_rx::ExternalIrq::define()
+///         .pin(2)
+///         .sense(r3_port_rx::IrqSense::FallingEdge)
+///         .filter(r3_port_rx::IrqFilter::PclkDiv64)
+///         .pin_function(0xd, 2) // PD2
+///         .priority(6)
+///         .start(button_handler)
+///         .finish(b);
+///     /* ... */
+/// }
+/// ```
+pub struct ExternalIrq;
+
+impl ExternalIrq {
+    /// Construct an `ExternalIrqDefiner`.
+    pub const fn define() -> ExternalIrqDefiner {
+        ExternalIrqDefiner {
+            pin: None,
+            sense: IrqSense::LowLevel,
+            filter: None,
+            pin_function: None,
+            mpc_base: 0x0008_c100,
+            priority: None,
+            enabled: true,
+            start: None,
+        }
+    }
+}
+
+/// The builder for an external interrupt pin. Created by
+/// [`ExternalIrq::define`].
+#[must_use = "must call `finish()` to complete registration"]
+pub struct ExternalIrqDefiner {
+    pub(crate) pin: Option<usize>,
+    pub(crate) sense: IrqSense,
+    pub(crate) filter: Option<IrqFilter>,
+    pub(crate) pin_function: Option<usize>,
+    pub(crate) mpc_base: usize,
+    pub(crate) priority: Option<InterruptPriority>,
+    pub(crate) enabled: bool,
+    pub(crate) start: Option<fn()>,
+}
+
+impl ExternalIrqDefiner {
+    /// \[**Required**\] Specify the external interrupt pin number (`0..16`).
+    pub const fn pin(self, pin: usize) -> Self {
+        assert!(pin < NUM_IRQ_PINS, "`pin` is out of range");
+        Self {
+            pin: Some(pin),
+            ..self
+        }
+    }
+
+    /// Specify the detection sense. Defaults to [`IrqSense::LowLevel`].
+    pub const fn sense(self, sense: IrqSense) -> Self {
+        Self { sense, ..self }
+    }
+
+    /// Enable the digital filter with the specified sampling clock. The
+    /// digital filter is disabled by default. Requires ICUb or ICUd
+    /// ([`ThreadingOptions::ICU_VARIANT`]).
+    ///
+    /// [`ThreadingOptions::ICU_VARIANT`]: crate::ThreadingOptions::ICU_VARIANT
+    pub const fn filter(self, filter: IrqFilter) -> Self {
+        Self {
+            filter: Some(filter),
+            ..self
+        }
+    }
+
+    /// Assign the external interrupt pin to the port pin `P{port}{bit}` by
+    /// setting `PmnPFS.ISEL` of Multi-Function Pin Controller (MPC). Ports
+    /// `PA`–`PJ` are numbered `10`–`18`.
+    ///
+    /// This is only applicable to devices having MPC (e.g., RX63N). The pin
+    /// function is left unchanged by default.
+    pub const fn pin_function(self, port: usize, bit: usize) -> Self {
+        assert!(port < 19 && bit < 8, "pin is out of range");
+        Self {
+            pin_function: Some(port * 8 + bit),
+            ..self
+        }
+    }
+
+    /// Specify the base address of the memory-mapped registers exposed by
+    /// Multi-Function Pin Controller. Defaults to `0x0008_c100`.
+    pub const fn mpc_base(self, mpc_base: usize) -> Self {
+        Self { mpc_base, ..self }
+    }
+
+    /// Specify the initial priority of the interrupt line. This requires
+    /// [`ThreadingOptions::INTERRUPT_LINE_IPR_INDEX`].
+    ///
+    /// [`ThreadingOptions::INTERRUPT_LINE_IPR_INDEX`]: crate::ThreadingOptions::INTERRUPT_LINE_IPR_INDEX
+    pub const fn priority(self, priority: InterruptPriority) -> Self {
+        Self {
+            priority: Some(priority),
+            ..self
+        }
+    }
+
+    /// Specify whether the interrupt line should be enabled at boot time.
+    /// Defaults to `true`.
+    pub const fn enabled(self, enabled: bool) -> Self {
+        Self { enabled, ..self }
+    }
+
+    /// Specify the interrupt handler to attach to the interrupt line.
+    pub const fn start(self, start: fn()) -> Self {
+        Self {
+            start: Some(start),
+            ..self
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! The implementation of external interrupt pins (`IRQn`).
+use r3_core::kernel::{traits, Cfg, InterruptLine, StartupHook, StaticInterruptHandler};
+use r3_kernel::System;
+use rsrx::{icub, mpc};
+use tock_registers::{
+    fields::FieldValue,
+    interfaces::{ReadWriteable, Writeable},
+};
+
+use crate::{
+    irq::cfg::{ExternalIrqDefiner, IrqFilter, IrqSense, IRQ0_INTERRUPT_NUM},
+    threading::imp::PortInstance,
+    IcuVariant,
+};
+
+/// The pin configuration applied by [`configure`].
+#[derive(Clone, Copy)]
+struct IrqConfig {
+    pin: usize,
+    sense: IrqSense,
+    filter: Option<IrqFilter>,
+    /// `(MPC base address, PFS index)`
+    pin_function: Option<(usize, usize)>,
+}
+
+impl ExternalIrqDefiner {
+    /// Complete the definition of an external interrupt pin.
+    pub const fn finish<C, Traits>(self, b: &mut Cfg<C>)
+    where
+        C: ~const traits::CfgBase<System = System<Traits>> + ~const traits::CfgInterruptLine,
+        Traits: PortInstance,
+    {
+        let pin = match self.pin {
+            Some(x) => x,
+            None => panic!("`pin` is not specified"),
+        };
+        let num = IRQ0_INTERRUPT_NUM + pin;
+
+        if self.filter.is_some() {
+            assert!(
+                !matches!(Traits::ICU_VARIANT, IcuVariant::Icua),
+                "the digital filter requires ICUb or ICUd"
+            );
+        }
+
+        let mut line = InterruptLine::define().line(num).enabled(self.enabled);
+        if let Some(priority) = self.priority {
+            line = line.priority(priority);
+        }
+        line.finish(b);
+
+        if let Some(start) = self.start {
+            StaticInterruptHandler::define()
+                .line(num)
+                .start(start)
+                .finish(b);
+        }
+
+        let config = IrqConfig {
+            pin,
+            sense: self.sense,
+            filter: self.filter,
+            pin_function: match self.pin_function {
+                Some(i) => Some((self.mpc_base, i)),
+                None => None,
+            },
+        };
+        StartupHook::define()
+            .start(move || configure::<Traits>(config))
+            .finish(b);
+    }
+}
+
+/// Configure an external interrupt pin. Called by a startup hook, i.e., with
+/// CPU Lock active.
+fn configure<Traits: PortInstance>(config: IrqConfig) {
+    // `IRQCRn` is located at the same offset in all ICU flavors
+    let icu = unsafe { &*(Traits::ICU_BASE as *const icub::Registers) };
+    let IrqConfig { pin, .. } = config;
+
+    if !matches!(Traits::ICU_VARIANT, IcuVariant::Icua) {
+        let (flte, fltc) = if pin < 8 {
+            (&icu.irqflte0, &icu.irqfltc0)
+        } else {
+            (&icu.irqflte1, &icu.irqfltc1)
+        };
+        let bit = pin % 8;
+
+        // Disable the digital filter while changing the sampling clock
+        flte.modify(FieldValue::<u8, _>::new(1, bit, 0));
+        if let Some(filter) = config.filter {
+            fltc.modify(FieldValue::<u16, _>::new(0b11, bit * 2, filter as u16));
+            flte.modify(FieldValue::<u8, _>::new(1, bit, 1));
+        }
+    }
+
+    if let Some((mpc_base, pfs_index)) = config.pin_function {
+        let mpc = unsafe { &*(mpc_base as *const mpc::Registers) };
+        mpc.pwpr.write(mpc::WriteProtect::B0WI::WriteEnabled);
+        mpc.pwpr.write(mpc::WriteProtect::PFSWE::WriteEnabled);
+        mpc.pfs[pfs_index].modify(mpc::PinFunctionControl::ISEL::UsedAsIrq);
+        mpc.pwpr.write(mpc::WriteProtect::PFSWE::WriteDisabled);
+        mpc.pwpr.write(mpc::WriteProtect::B0WI::WriteDisabled);
+    }
+
+    icu.irqcr[pin].write(icub::IrqControl::IRQMD.val(config.sense as u8));
+
+    // Changing the detection sense or the pin function may cause a spurious
+    // interrupt request
+    icu.ir[IRQ0_INTERRUPT_NUM + pin].set(0);
+}
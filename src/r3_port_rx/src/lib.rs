@@ -34,14 +34,13 @@ pub mod threading {
     pub mod imp;
 }
 
-// TODO: Startup
-// /// The standard startup code.
-// #[doc(hidden)]
-// pub mod startup {
-//     pub mod cfg;
-//     #[cfg(target_os = "none")]
-//     pub mod imp;
-// }
+/// The standard startup code.
+#[doc(hidden)]
+pub mod startup {
+    pub mod cfg;
+    #[cfg(target_os = "none")]
+    pub mod imp;
+}
 
 /// The tickless [`r3_kernel::PortTimer`] implementation based on CMT.
 #[doc(hidden)]
@@ -51,10 +50,46 @@ pub mod cmt {
     pub mod imp;
 }
 
+/// The tickful [`r3_kernel::PortTimer`] implementation based on CMT.
+#[doc(hidden)]
+pub mod cmt_periodic {
+    pub mod cfg;
+    #[cfg(target_os = "none")]
+    pub mod imp;
+}
+
+/// The tickless [`r3_kernel::PortTimer`] implementation based on CMTW.
+#[doc(hidden)]
+pub mod cmtw {
+    pub mod cfg;
+    #[cfg(target_os = "none")]
+    pub mod imp;
+}
+
+/// The tickless [`r3_kernel::PortTimer`] implementation based on TMR.
+#[doc(hidden)]
+pub mod tmr {
+    pub mod cfg;
+    #[cfg(target_os = "none")]
+    pub mod imp;
+}
+
+/// External interrupt pins (`IRQn`).
+#[doc(hidden)]
+pub mod irq {
+    pub mod cfg;
+    #[cfg(target_os = "none")]
+    pub mod imp;
+}
+
 pub use self::cmt::cfg::*;
+pub use self::cmtw::cfg::*;
+pub use self::irq::cfg::*;
+pub use self::startup::cfg::*;
 pub use self::threading::cfg::*;
+pub use self::tmr::cfg::*;
 
-/// Used by `use_port!`
+/// Used by `use_port!` and `use_startup!`
 #[doc(hidden)]
 #[cfg(target_os = "none")]
 pub extern crate core;
@@ -64,12 +99,13 @@ pub extern crate r3_core;
 /// Used by `use_port!`
 #[doc(hidden)]
 pub extern crate r3_kernel;
-/// Used by `use_cmt!`
+/// Used by `use_cmt!`, `use_cmt_periodic!`, `use_cmtw!`, and `use_tmr!`
 #[doc(hidden)]
 pub extern crate r3_portkit;
 
 /// An abstract inferface to a port timer driver. Implemented by
-/// [`use_cmt!`][].
+/// [`use_cmt!`][], [`use_cmt_periodic!`][], [`use_cmtw!`][], and
+/// [`use_tmr!`][].
 pub trait Timer {
     /// Initialize the driver. This will be called just before entering
     /// [`PortToKernel::boot`].
@@ -0,0 +1,297 @@
+//! The public interface for the standard startup code.
+
+/// Generate the standard startup code, which initializes the processor state
+/// and the memory and then hands off to [`EntryPoint::start`].
+/// **Requires [`StartupOptions`] and [`EntryPoint`].**
+///
+/// [`EntryPoint`]: crate::EntryPoint
+/// [`EntryPoint::start`]: crate::EntryPoint::start
+///
+/// This macro generates the following items:
+///
+///  - The fixed vector table, placed in the `.fixed_vector_table` section. The
+///    reset vector points to the reset handler, and the vectors of the
+///    exceptions listed in [`Exception`] point to exception handlers, which
+///    collect [`CrashInfo`] and call [`StartupOptions::EXCEPTION_HOOK`]. If
+///    [`StartupOptions::NMI_HOOK`] is specified, the non-maskable interrupt
+///    vector points to an NMI handler, which calls the hook and resumes the
+///    interrupted code.
+///  - The reset handler, placed in the `.text.start` section.
+///  - A `start` symbol, which is an unmangled alias of the reset handler. The
+///    linker script can specify it as the entry point by `ENTRY(start)`.
+///  - An `_abort` symbol, which the compiler might reference.
+///
+/// The reset handler performs the following steps:
+///
+///  1. Initialize the stack pointers (`ISP` and `USP`).
+///  2. Initialize `FPSW` (if [`StartupOptions::FPSW_INIT`] is `Some(_)`).
+///  3. Call [`StartupOptions::PRE_INIT_HOOK`].
+///  4. Initialize `.data` and `.bss`.
+///  5. Select the boot stack pointer (if [`StartupOptions::USE_USP_FOR_BOOT`]
+///     is `true`).
+///  6. Enable the NMI sources specified by [`StartupOptions::NMI_SOURCES`].
+///  7. Call [`EntryPoint::start`].
+///
+/// The linker script must define the sections and symbols listed below.
+///
+/// |     Symbol     |           Description           |
+/// | -------------- | ------------------------------- |
+/// | `__sdata`      | The start address of `.data`    |
+/// | `__edata`      | The end address of `.data`      |
+/// | `__sidata`     | The load address of `.data`     |
+/// | `__sbss`       | The start address of `.bss`     |
+/// | `__ebss`       | The end address of `.bss`       |
+/// | `_stack_start` | The default initial `ISP` value |
+///
+/// ```rust,ignore
+/// r3_port_rx::use_port!(unsafe struct SystemTraits);
+/// r3_port_rx::use_startup!(unsafe SystemTraits);
+///
+/// impl r3_port_rx::StartupOptions for SystemTraits {}
+/// ```
+///
+/// # Safety
+///
+///  - The target must really be a bare-metal RX environment, and the generated
+///    code must be the first code to run after a reset.
+///  - `StartupOptions` must be configured correctly.
+///  - The application must not define another fixed vector table, `start`, or
+///    `_abort`.
+///
+#[macro_export]
+macro_rules! use_startup {
+    (unsafe $Traits:ty) => {
+        const _: () = {
+            use $crate::startup::imp;
+
+            #[link_section = ".fixed_vector_table"]
+            #[used]
+            static FIXED_VECTOR_TABLE: imp::FixedVectorTable =
+                imp::fixed_vector_table::<$Traits>();
+
+            // FIXME: Work-around for DCE not honoring `sym` operands
+            //        <https://github.com/rust-lang/rustc_codegen_gcc/issues/157>
+            #[used]
+            static KEEP_SYMBOLS: imp::KeepSymbols = imp::KeepSymbols::new::<$Traits>();
+
+            const _: () = imp::validate::<$Traits>();
+        };
+
+        // FIXME: Why does `core::intrinsics::const_eval_select::<(&str, usize,
+        // usize), core::str::slice_error_fail_ct, core::str::slice_error_fail_rt,
+        // !>` contain a reference to this symbol?
+        $crate::core::arch::global_asm!(
+            "
+            .global _abort
+        _abort:
+            bra _abort
+            "
+        );
+
+        // Export the reset handler under an unmangled name so that the linker
+        // script can refer to it
+        $crate::core::arch::global_asm!(
+            "
+            .global start
+            .set start, _{start}
+            ",
+            start = sym $crate::startup::imp::start::<$Traits>,
+        );
+    };
+}
+
+/// The options for [`use_startup!`].
+pub trait StartupOptions {
+    /// The initial value of the interrupt stack pointer (`ISP`). Defaults to
+    /// `None`, in which case the address of the linker-defined symbol
+    /// `_stack_start` is used.
+    const INTERRUPT_STACK_TOP: Option<usize> = None;
+
+    /// The initial value of the user stack pointer (`USP`). Defaults to
+    /// `None`, in which case `ISP`'s initial value is used.
+    ///
+    /// `USP` is only used by the boot process, so it can be identical to `ISP`
+    /// unless unmanaged interrupts or non-maskable interrupts can be taken
+    /// during the boot process (see [`EntryPoint::start`][]). Therefore, this
+    /// must be `Some(_)` and must not overlap with the interrupt stack if
+    /// [`Self::NMI_HOOK`] is specified and [`Self::USE_USP_FOR_BOOT`] is
+    /// `true`.
+    ///
+    /// [`EntryPoint::start`]: crate::EntryPoint::start
+    const USER_STACK_TOP: Option<usize> = None;
+
+    /// Select `USP` (`PSW.U = 1`) before calling [`EntryPoint::start`][].
+    /// Defaults to `true`. If this is `false`, the boot process runs on the
+    /// interrupt stack.
+    ///
+    /// [`EntryPoint::start`]: crate::EntryPoint::start
+    const USE_USP_FOR_BOOT: bool = true;
+
+    /// The initial value of the floating-point status word (`FPSW`). Defaults
+    /// to `Some(0)`. Set this to `None` if the processor doesn't have a
+    /// floating-point unit.
+    const FPSW_INIT: Option<u32> = Some(0);
+
+    /// The function to call before initializing `.data` and `.bss`, e.g., to
+    /// configure the clock generator. Defaults to `None`.
+    ///
+    /// The function must not access any statics because they aren't
+    /// initialized yet. It runs on the interrupt stack with all interrupts
+    /// disabled.
+    const PRE_INIT_HOOK: Option<unsafe fn()> = None;
+
+    /// The function to call when an exception listed in [`Exception`] occurs.
+    /// Defaults to `None`, in which case a panic is raised with the crash
+    /// information.
+    ///
+    /// The function runs on the interrupt stack with all interrupts disabled.
+    /// It can't return because the port doesn't know how to resume the
+    /// execution.
+    const EXCEPTION_HOOK: Option<fn(&CrashInfo) -> !> = None;
+
+    /// The NMI sources to enable during boot. Defaults to
+    /// [`NmiSources::NONE`].
+    ///
+    /// Note that NMI sources can't be disabled once enabled.
+    const NMI_SOURCES: NmiSources = NmiSources::NONE;
+
+    /// The function to call when a non-maskable interrupt is requested.
+    /// Defaults to `None`, in which case a non-maskable interrupt is handled as
+    /// an exception ([`Exception::NonMaskableInterrupt`]).
+    ///
+    /// The function receives the NMI status flags (`NMISR`). The flags that
+    /// can be cleared by `NMICLR` are cleared after the function returns, and
+    /// the interrupted code is resumed. The other flags (the voltage
+    /// monitoring flag of ICUa and the RAM error flag of ICUd) must be cleared
+    /// through the respective modules by the function.
+    ///
+    /// The function runs on the interrupt stack with all interrupts disabled.
+    /// It can preempt any code, including the kernel's critical sections, so
+    /// it must not call kernel services. It must not use the accumulator
+    /// (`ACC`), which is not saved.
+    ///
+    /// Because the interrupted code is resumed, no code may use the memory
+    /// locations beneath `ISP` as its stack. `use_startup!` rejects this
+    /// option unless [`ThreadingOptions::RESUMABLE_NMI`][] is `true`, which
+    /// stops the dispatcher from borrowing the interrupt stack, and (if
+    /// [`Self::USE_USP_FOR_BOOT`] is `true`) [`Self::USER_STACK_TOP`] is
+    /// specified.
+    ///
+    /// [`ThreadingOptions::RESUMABLE_NMI`]: crate::ThreadingOptions::RESUMABLE_NMI
+    const NMI_HOOK: Option<fn(NmiStatus)> = None;
+}
+
+/// The NMI sources to enable. See [`StartupOptions::NMI_SOURCES`].
+///
+/// The available sources depend on the ICU variant
+/// ([`ThreadingOptions::ICU_VARIANT`]). `use_startup!` rejects the sources not
+/// supported by the ICU variant.
+///
+/// [`ThreadingOptions::ICU_VARIANT`]: crate::ThreadingOptions::ICU_VARIANT
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NmiSources {
+    /// The NMI pin (`NMIER.NMIEN`), triggered by the specified edge
+    /// (`NMICR.NMIMD`).
+    pub pin: Option<NmiEdge>,
+    /// The voltage monitoring interrupt (`NMIER.LVDEN` of ICUa) or the voltage
+    /// monitoring 1 interrupt (`NMIER.LVD1EN` of ICUb and ICUd).
+    pub voltage_monitoring: bool,
+    /// The voltage monitoring 2 interrupt (`NMIER.LVD2EN`). Requires ICUb or
+    /// ICUd.
+    pub voltage_monitoring2: bool,
+    /// The oscillation stop detection interrupt (`NMIER.OSTEN`).
+    pub oscillation_stop: bool,
+    /// The WDT underflow/refresh error (`NMIER.WDTEN`). Requires ICUd.
+    pub watchdog: bool,
+    /// The IWDT underflow/refresh error (`NMIER.IWDTEN`). Requires ICUd.
+    pub independent_watchdog: bool,
+    /// The RAM error interrupt (`NMIER.RAMEN`). Requires ICUd.
+    pub ram_error: bool,
+}
+
+impl NmiSources {
+    /// No NMI sources are enabled.
+    pub const NONE: Self = Self {
+        pin: None,
+        voltage_monitoring: false,
+        voltage_monitoring2: false,
+        oscillation_stop: false,
+        watchdog: false,
+        independent_watchdog: false,
+        ram_error: false,
+    };
+}
+
+/// The detection edge of the NMI pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NmiEdge {
+    Falling,
+    Rising,
+}
+
+/// The NMI status flags passed to [`StartupOptions::NMI_HOOK`]. The flags not
+/// supported by the ICU variant ([`ThreadingOptions::ICU_VARIANT`]) are always
+/// `false`.
+///
+/// [`ThreadingOptions::ICU_VARIANT`]: crate::ThreadingOptions::ICU_VARIANT
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NmiStatus {
+    /// The NMI pin requested an interrupt (`NMISR.NMIST`).
+    pub pin: bool,
+    /// The voltage monitoring circuit requested an interrupt
+    /// (`NMISR.LVDST` of ICUa or `NMISR.LVD1ST` of ICUb and ICUd).
+    pub voltage_monitoring: bool,
+    /// The voltage monitoring 2 circuit requested an interrupt
+    /// (`NMISR.LVD2ST`).
+    pub voltage_monitoring2: bool,
+    /// The oscillation stop detection circuit requested an interrupt
+    /// (`NMISR.OSTST`).
+    pub oscillation_stop: bool,
+    /// WDT requested an interrupt (`NMISR.WDTST`).
+    pub watchdog: bool,
+    /// IWDT requested an interrupt (`NMISR.IWDTST`).
+    pub independent_watchdog: bool,
+    /// A RAM error was detected (`NMISR.RAMST`).
+    pub ram_error: bool,
+}
+
+/// An exception handled by the exception handlers generated by
+/// [`use_startup!`]. The discriminants are the vector numbers in the fixed
+/// vector table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum Exception {
+    /// A privileged instruction was executed in User mode.
+    PrivilegedInstruction = 20,
+    /// A memory access violated the memory protection unit's settings.
+    Access = 21,
+    /// An undefined instruction was executed.
+    UndefinedInstruction = 23,
+    /// A floating-point operation raised an exception enabled by `FPSW`.
+    FloatingPoint = 25,
+    /// A non-maskable interrupt was requested, and
+    /// [`StartupOptions::NMI_HOOK`] is `None`.
+    NonMaskableInterrupt = 30,
+}
+
+/// The processor state captured by an exception handler generated by
+/// [`use_startup!`].
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct CrashInfo {
+    /// The exception that occurred.
+    pub exception: Exception,
+    /// The value of `FPSW`. This is `0` if [`StartupOptions::FPSW_INIT`] is
+    /// `None`.
+    pub fpsw: u32,
+    /// The value of `USP`.
+    pub usp: u32,
+    /// The values of the general-purpose registers `R0`–`R15`. `regs[0]` is
+    /// the stack pointer that was in use when the exception occurred.
+    pub regs: [u32; 16],
+    /// The address of the instruction that caused the exception (or the next
+    /// instruction to execute, depending on the exception type).
+    pub pc: u32,
+    /// The value of `PSW` before the exception occurred.
+    pub psw: u32,
+}
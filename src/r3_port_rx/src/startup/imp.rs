@@ -0,0 +1,415 @@
+//! The implementation of the standard startup code.
+use r3_portkit::pptext::pp_asm;
+use rsrx::{icua, icub, icud};
+use tock_registers::interfaces::{Readable, Writeable};
+
+use crate::{
+    startup::cfg::{CrashInfo, Exception, NmiEdge, NmiSources, NmiStatus, StartupOptions},
+    EntryPoint, IcuVariant,
+};
+
+pub type FixedVectorTable = [unsafe extern "C" fn() -> !; 32];
+
+/// The vector number of the reset vector in the fixed vector table.
+const RESET_VECTOR: usize = 31;
+
+/// `PSW.U`
+const PSW_U: u32 = 1 << 17;
+
+/// Validate the configuration. Used by `use_startup!`.
+pub const fn validate<Traits: StartupOptions + EntryPoint>() {
+    let sources = Traits::NMI_SOURCES;
+    match Traits::PORT_ICU_VARIANT {
+        IcuVariant::Icua => assert!(
+            !sources.voltage_monitoring2
+                && !sources.watchdog
+                && !sources.independent_watchdog
+                && !sources.ram_error,
+            "`NMI_SOURCES` contains an NMI source not supported by ICUa"
+        ),
+        IcuVariant::Icub => assert!(
+            !sources.watchdog && !sources.independent_watchdog && !sources.ram_error,
+            "`NMI_SOURCES` contains an NMI source not supported by ICUb"
+        ),
+        IcuVariant::Icud => {}
+    }
+
+    if Traits::NMI_HOOK.is_some() {
+        // A non-maskable interrupt handler that returns to the interrupted
+        // code must not overwrite the stack in use
+        assert!(
+            Traits::PORT_RESUMABLE_NMI,
+            "`NMI_HOOK` requires `ThreadingOptions::RESUMABLE_NMI`"
+        );
+        assert!(
+            !Traits::USE_USP_FOR_BOOT || Traits::USER_STACK_TOP.is_some(),
+            "`NMI_HOOK` requires `USER_STACK_TOP` if `USE_USP_FOR_BOOT` is `true`"
+        );
+    }
+}
+
+/// Generate the fixed vector table for the specified system trait type.
+pub const fn fixed_vector_table<Traits: StartupOptions + EntryPoint>() -> FixedVectorTable {
+    let mut table = [unhandled_exception as _; 32];
+    table[RESET_VECTOR] = start::<Traits>;
+
+    macro_rules! exceptions {
+        ($($exc:ident),*) => {$(
+            table[Exception::$exc as usize] =
+                exception_handler::<Traits, { Exception::$exc as u32 }>;
+        )*};
+    }
+    exceptions!(
+        PrivilegedInstruction,
+        Access,
+        UndefinedInstruction,
+        FloatingPoint,
+        NonMaskableInterrupt
+    );
+
+    if Traits::NMI_HOOK.is_some() {
+        table[Exception::NonMaskableInterrupt as usize] = nmi_handler::<Traits>;
+    }
+
+    table
+}
+
+unsafe extern "C" fn unhandled_exception() -> ! {
+    panic!("unhandled exception")
+}
+
+/// FIXME: Work-around for DCE not honoring `sym` operands
+/// <https://github.com/rust-lang/rustc_codegen_gcc/issues/157>
+pub struct KeepSymbols {
+    _main: unsafe extern "C" fn() -> !,
+    _pre_init: unsafe extern "C" fn(),
+    _handle_exception: unsafe extern "C" fn(*mut CrashInfo) -> !,
+    _handle_nmi: unsafe extern "C" fn(),
+}
+
+impl KeepSymbols {
+    pub const fn new<Traits: StartupOptions + EntryPoint>() -> Self {
+        Self {
+            _main: main::<Traits>,
+            _pre_init: pre_init::<Traits>,
+            _handle_exception: handle_exception::<Traits>,
+            _handle_nmi: handle_nmi::<Traits>,
+        }
+    }
+}
+
+/// The reset handler. Exported as `start` by `use_startup!`.
+#[naked]
+#[link_section = ".text.start"]
+pub unsafe extern "C" fn start<Traits: StartupOptions + EntryPoint>() -> ! {
+    unsafe {
+        pp_asm!(
+            "
+                # Set the stack pointers
+            .ifne {HAS_INTERRUPT_STACK_TOP}
+                mvtc #{INTERRUPT_STACK_TOP}, isp
+            .else
+                mvtc #_stack_start, isp
+            .endif
+            .ifne {HAS_USER_STACK_TOP}
+                mvtc #{USER_STACK_TOP}, usp
+            .else
+                mvfc isp, r1
+                mvtc r1, usp
+            .endif
+
+            .ifne {HAS_FPSW_INIT}
+                mvtc #{FPSW_INIT}, fpsw
+            .endif
+
+            .ifne {HAS_PRE_INIT_HOOK}
+                bsr.a _{pre_init}
+            .endif
+
+                # Initialize .data
+                mov #__sidata, r2
+                mov #__sdata, r1
+                mov #(__edata - __sdata), r3
+                smovf
+
+                # Initialize .bss
+                mov #__sbss, r1
+                mov #(__ebss - __sbss), r3
+                mov #0, r2
+                sstr
+
+            .ifne {USE_USP_FOR_BOOT}
+                # Select USP
+                setpsw u
+            .endif
+
+                bra.a _{main}
+            ",
+            HAS_INTERRUPT_STACK_TOP = const Traits::INTERRUPT_STACK_TOP.is_some() as u32,
+            INTERRUPT_STACK_TOP = const Traits::INTERRUPT_STACK_TOP.unwrap_or(0),
+            HAS_USER_STACK_TOP = const Traits::USER_STACK_TOP.is_some() as u32,
+            USER_STACK_TOP = const Traits::USER_STACK_TOP.unwrap_or(0),
+            HAS_FPSW_INIT = const Traits::FPSW_INIT.is_some() as u32,
+            FPSW_INIT = const Traits::FPSW_INIT.unwrap_or(0),
+            HAS_PRE_INIT_HOOK = const Traits::PRE_INIT_HOOK.is_some() as u32,
+            USE_USP_FOR_BOOT = const Traits::USE_USP_FOR_BOOT as u32,
+            pre_init = sym pre_init::<Traits>,
+            main = sym main::<Traits>,
+            options(noreturn),
+        );
+    }
+}
+
+/// Calls [`StartupOptions::PRE_INIT_HOOK`].
+unsafe extern "C" fn pre_init<Traits: StartupOptions>() {
+    if let Some(hook) = Traits::PRE_INIT_HOOK {
+        // Safety: The hook is called in the context documented by
+        // `StartupOptions::PRE_INIT_HOOK`
+        unsafe { hook() };
+    }
+}
+
+unsafe extern "C" fn main<Traits: StartupOptions + EntryPoint>() -> ! {
+    enable_nmi_sources::<Traits>();
+
+    // Safety: This is the only call site, and the processor state meets the
+    // requirements of `EntryPoint::start`
+    unsafe { Traits::start() }
+}
+
+/// The exception handler for the exception `EXCEPTION`. Builds [`CrashInfo`]
+/// on the interrupt stack and passes it to [`handle_exception`].
+#[naked]
+unsafe extern "C" fn exception_handler<Traits: StartupOptions, const EXCEPTION: u32>() -> ! {
+    unsafe {
+        pp_asm!(
+            "
+                # The processor has pushed PSW and PC to the interrupt stack.
+                # Push the rest of `CrashInfo` in the reverse order.
+                pushm r1-r15
+
+                # r4 = ISP before the exception
+                add #68, r0, r4
+                mvfc usp, r3
+            .ifne {HAS_FPU}
+                mvfc fpsw, r2
+            .else
+                mov #0, r2
+            .endif
+                mov #{EXCEPTION}, r1
+                pushm r1-r4
+
+                mov r0, r1
+                bra.a _{handle_exception}
+            ",
+            HAS_FPU = const Traits::FPSW_INIT.is_some() as u32,
+            EXCEPTION = const EXCEPTION,
+            handle_exception = sym handle_exception::<Traits>,
+            options(noreturn),
+        );
+    }
+}
+
+/// Calls [`StartupOptions::EXCEPTION_HOOK`].
+unsafe extern "C" fn handle_exception<Traits: StartupOptions>(info: *mut CrashInfo) -> ! {
+    // Safety: `exception_handler` passes a pointer to a `CrashInfo` it built
+    // on the stack
+    let info = unsafe { &mut *info };
+
+    // `regs[0]` is ISP at this point. If the exception was taken while USP
+    // was selected, the stack pointer in use was USP.
+    if info.psw & PSW_U != 0 {
+        info.regs[0] = info.usp;
+    }
+
+    if let Some(hook) = Traits::EXCEPTION_HOOK {
+        hook(info);
+    } else {
+        panic!("unhandled exception: {:?}", info);
+    }
+}
+
+/// Enable the NMI sources specified by [`StartupOptions::NMI_SOURCES`].
+fn enable_nmi_sources<Traits: StartupOptions + EntryPoint>() {
+    let sources = Traits::NMI_SOURCES;
+    if sources == NmiSources::NONE {
+        return;
+    }
+
+    // The detection edge must be configured before enabling the NMI pin.
+    // `NMICR` is identical in all ICU variants.
+    if let Some(edge) = sources.pin {
+        nmi_icu::<Traits, icua::Registers>()
+            .nmicr
+            .write(match edge {
+                NmiEdge::Falling => icua::NmiPinInterruptControl::NMIMD::FallingEdge,
+                NmiEdge::Rising => icua::NmiPinInterruptControl::NMIMD::RisingEdge,
+            });
+    }
+
+    // Discard the requests made before enabling the NMI sources, and then
+    // enable the NMI sources
+    match Traits::PORT_ICU_VARIANT {
+        IcuVariant::Icua => {
+            let icu = nmi_icu::<Traits, icua::Registers>();
+            icu.nmiclr
+                .write(icua::NmiClear::NMICLR::Clear + icua::NmiClear::OSTCLR::Clear);
+            icu.nmier.write(
+                icua::NmiEnable::NMIEN.val(sources.pin.is_some() as u8)
+                    + icua::NmiEnable::LVDEN.val(sources.voltage_monitoring as u8)
+                    + icua::NmiEnable::OSTEN.val(sources.oscillation_stop as u8),
+            );
+        }
+        IcuVariant::Icub => {
+            let icu = nmi_icu::<Traits, icub::Registers>();
+            icu.nmiclr.write(
+                icub::NmiClear::NMICLR::Clear
+                    + icub::NmiClear::OSTCLR::Clear
+                    + icub::NmiClear::LVD1CLR::Clear
+                    + icub::NmiClear::LVD2CLR::Clear,
+            );
+            icu.nmier.write(
+                icub::NmiEnable::NMIEN.val(sources.pin.is_some() as u8)
+                    + icub::NmiEnable::OSTEN.val(sources.oscillation_stop as u8)
+                    + icub::NmiEnable::LVD1EN.val(sources.voltage_monitoring as u8)
+                    + icub::NmiEnable::LVD2EN.val(sources.voltage_monitoring2 as u8),
+            );
+        }
+        IcuVariant::Icud => {
+            let icu = nmi_icu::<Traits, icud::Registers>();
+            icu.nmiclr.write(
+                icud::NmiClear::NMICLR::Clear
+                    + icud::NmiClear::OSTCLR::Clear
+                    + icud::NmiClear::WDTCLR::Clear
+                    + icud::NmiClear::IWDTCLR::Clear
+                    + icud::NmiClear::LVD1CLR::Clear
+                    + icud::NmiClear::LVD2CLR::Clear,
+            );
+            icu.nmier.write(
+                icud::NmiEnable::NMIEN.val(sources.pin.is_some() as u8)
+                    + icud::NmiEnable::OSTEN.val(sources.oscillation_stop as u8)
+                    + icud::NmiEnable::WDTEN.val(sources.watchdog as u8)
+                    + icud::NmiEnable::IWDTEN.val(sources.independent_watchdog as u8)
+                    + icud::NmiEnable::LVD1EN.val(sources.voltage_monitoring as u8)
+                    + icud::NmiEnable::LVD2EN.val(sources.voltage_monitoring2 as u8)
+                    + icud::NmiEnable::RAMEN.val(sources.ram_error as u8),
+            );
+        }
+    }
+}
+
+/// Get the ICU registers of the specified variant (`icua::Registers`,
+/// `icub::Registers`, or `icud::Registers`).
+#[inline(always)]
+fn nmi_icu<Traits: StartupOptions + EntryPoint, Registers>() -> &'static Registers {
+    // Safety: `ICU_BASE` is verified by the user of `use_port!`
+    unsafe { &*(Traits::PORT_ICU_BASE as *const Registers) }
+}
+
+/// The non-maskable interrupt handler. Saves the general-purpose registers
+/// and `FPSW` to the interrupt stack and calls [`handle_nmi`].
+#[naked]
+unsafe extern "C" fn nmi_handler<Traits: StartupOptions + EntryPoint>() -> ! {
+    unsafe {
+        pp_asm!(
+            "
+                # The processor has pushed PSW and PC to the interrupt stack
+                pushm r1-r15
+            .ifne {HAS_FPU}
+                pushc fpsw
+            .endif
+
+                bsr.a _{handle_nmi}
+
+            .ifne {HAS_FPU}
+                popc fpsw
+            .endif
+                popm r1-r15
+                rte
+            ",
+            HAS_FPU = const Traits::FPSW_INIT.is_some() as u32,
+            handle_nmi = sym handle_nmi::<Traits>,
+            options(noreturn),
+        );
+    }
+}
+
+/// Calls [`StartupOptions::NMI_HOOK`] and clears the NMI status flags.
+unsafe extern "C" fn handle_nmi<Traits: StartupOptions + EntryPoint>() {
+    let status = read_nmi_status::<Traits>();
+
+    if let Some(hook) = Traits::NMI_HOOK {
+        hook(status);
+    }
+
+    // Clear only the flags observed above so that a request made during the
+    // hook is not lost
+    clear_nmi_status::<Traits>(status);
+}
+
+/// Read `NMISR` according to the ICU variant.
+fn read_nmi_status<Traits: StartupOptions + EntryPoint>() -> NmiStatus {
+    let mut status = NmiStatus {
+        pin: false,
+        voltage_monitoring: false,
+        voltage_monitoring2: false,
+        oscillation_stop: false,
+        watchdog: false,
+        independent_watchdog: false,
+        ram_error: false,
+    };
+
+    match Traits::PORT_ICU_VARIANT {
+        IcuVariant::Icua => {
+            let nmisr = nmi_icu::<Traits, icua::Registers>().nmisr.extract();
+            status.pin = nmisr.is_set(icua::NmiStatus::NMIST);
+            status.voltage_monitoring = nmisr.is_set(icua::NmiStatus::LVDST);
+            status.oscillation_stop = nmisr.is_set(icua::NmiStatus::OSTST);
+        }
+        IcuVariant::Icub => {
+            let nmisr = nmi_icu::<Traits, icub::Registers>().nmisr.extract();
+            status.pin = nmisr.is_set(icub::NmiStatus::NMIST);
+            status.oscillation_stop = nmisr.is_set(icub::NmiStatus::OSTST);
+            status.voltage_monitoring = nmisr.is_set(icub::NmiStatus::LVD1ST);
+            status.voltage_monitoring2 = nmisr.is_set(icub::NmiStatus::LVD2ST);
+        }
+        IcuVariant::Icud => {
+            let nmisr = nmi_icu::<Traits, icud::Registers>().nmisr.extract();
+            status.pin = nmisr.is_set(icud::NmiStatus::NMIST);
+            status.oscillation_stop = nmisr.is_set(icud::NmiStatus::OSTST);
+            status.watchdog = nmisr.is_set(icud::NmiStatus::WDTST);
+            status.independent_watchdog = nmisr.is_set(icud::NmiStatus::IWDTST);
+            status.voltage_monitoring = nmisr.is_set(icud::NmiStatus::LVD1ST);
+            status.voltage_monitoring2 = nmisr.is_set(icud::NmiStatus::LVD2ST);
+            status.ram_error = nmisr.is_set(icud::NmiStatus::RAMST);
+        }
+    }
+
+    status
+}
+
+/// Clear the specified flags of `NMISR` by writing `NMICLR` according to the
+/// ICU variant. The flags that `NMICLR` can't clear are left to
+/// [`StartupOptions::NMI_HOOK`].
+fn clear_nmi_status<Traits: StartupOptions + EntryPoint>(status: NmiStatus) {
+    match Traits::PORT_ICU_VARIANT {
+        IcuVariant::Icua => nmi_icu::<Traits, icua::Registers>().nmiclr.write(
+            icua::NmiClear::NMICLR.val(status.pin as u8)
+                + icua::NmiClear::OSTCLR.val(status.oscillation_stop as u8),
+        ),
+        IcuVariant::Icub => nmi_icu::<Traits, icub::Registers>().nmiclr.write(
+            icub::NmiClear::NMICLR.val(status.pin as u8)
+                + icub::NmiClear::OSTCLR.val(status.oscillation_stop as u8)
+                + icub::NmiClear::LVD1CLR.val(status.voltage_monitoring as u8)
+                + icub::NmiClear::LVD2CLR.val(status.voltage_monitoring2 as u8),
+        ),
+        IcuVariant::Icud => nmi_icu::<Traits, icud::Registers>().nmiclr.write(
+            icud::NmiClear::NMICLR.val(status.pin as u8)
+                + icud::NmiClear::OSTCLR.val(status.oscillation_stop as u8)
+                + icud::NmiClear::WDTCLR.val(status.watchdog as u8)
+                + icud::NmiClear::IWDTCLR.val(status.independent_watchdog as u8)
+                + icud::NmiClear::LVD1CLR.val(status.voltage_monitoring as u8)
+                + icud::NmiClear::LVD2CLR.val(status.voltage_monitoring2 as u8),
+        ),
+    }
+}
@@ -1,38 +1,485 @@
 use core::{fmt, ops::Range};
 use r3_core::kernel::{InterruptNum, InterruptPriority, ResultCode};
+use r3_kernel::{KernelTraits, TaskCb};
 
-// TODO: "Fast interrupts"
 /// The valid interrupt group priority values.
 ///
 /// Note that the value `0` (lowest) disables interrupts unless the
-/// corresponding interrupt line is designated as "fast interrupts".
+/// corresponding interrupt line is designated as "fast interrupts"
+/// ([`ThreadingOptions::FAST_INTERRUPT_NUM`]).
 pub const INTERRUPT_PRIORITY_RANGE: Range<InterruptPriority> = 0..16;
 
 /// The range of valid `InterruptNum`s.
 pub const INTERRUPT_NUM_RANGE: Range<InterruptNum> = 16..256;
 
+/// The maximum number of group interrupts in
+/// [`ThreadingOptions::GROUP_INTERRUPTS`].
+pub const MAX_GROUP_INTERRUPTS: usize = 8;
+
+/// The range of virtual `InterruptNum`s assigned to the interrupt sources of
+/// group interrupts. See [`group_interrupt_num`].
+pub const GROUP_INTERRUPT_NUM_RANGE: Range<InterruptNum> = 256..256 + MAX_GROUP_INTERRUPTS * 32;
+
+/// Get the virtual `InterruptNum` of the interrupt source `source` of the group
+/// interrupt [`ThreadingOptions::GROUP_INTERRUPTS`]`[group]`. `source` is the
+/// bit position of the interrupt source in the group's interrupt request
+/// register (e.g., `GRPBL0`).
+pub const fn group_interrupt_num(group: usize, source: usize) -> InterruptNum {
+    assert!(group < MAX_GROUP_INTERRUPTS, "`group` is out of range");
+    assert!(source < 32, "`source` is out of range");
+    GROUP_INTERRUPT_NUM_RANGE.start + group * 32 + source
+}
+
+/// Identifies the registers controlling a group interrupt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InterruptGroup {
+    /// ICUb `GROUPn` consisting of edge-detected interrupt sources (`GRPn`,
+    /// `GENn`, and `GCRn`). Requires [`IcuVariant::Icub`].
+    IcubEdge(usize),
+    /// ICUb `GROUPn` consisting of level-detected interrupt sources (`GRPn` and
+    /// `GENn`). Requires [`IcuVariant::Icub`].
+    IcubLevel(usize),
+    /// ICUd `GROUPBE0` (`GRPBE0`, `GENBE0`, and `GCRBE0`). Requires
+    /// [`IcuVariant::Icud`].
+    Be0,
+    /// ICUd `GROUPBLn` (`GRPBLn` and `GENBLn`). Requires [`IcuVariant::Icud`].
+    Bl(usize),
+    /// ICUd `GROUPALn` (`GRPALn` and `GENALn`). Requires [`IcuVariant::Icud`].
+    Al(usize),
+}
+
+/// A group interrupt to be demultiplexed by the port. See
+/// [`ThreadingOptions::GROUP_INTERRUPTS`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GroupInterrupt {
+    /// The vector number of the group interrupt.
+    pub num: InterruptNum,
+    /// The group interrupt's registers.
+    pub group: InterruptGroup,
+    /// The priority of the group interrupt's vector, which is shared by all
+    /// interrupt sources of the group. Must be in range `1..16`.
+    pub priority: InterruptPriority,
+}
+
+/// Get the index of the element of `groups` that uses the specified vector.
+#[cfg_attr(not(target_os = "none"), allow(dead_code))]
+pub(crate) const fn group_index_of_vector(
+    groups: &[GroupInterrupt],
+    num: InterruptNum,
+) -> Option<usize> {
+    let mut i = 0;
+    while i < groups.len() {
+        if groups[i].num == num {
+            return Some(i);
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Assigns an interrupt source to a software-configurable interrupt vector.
+/// See [`ThreadingOptions::INTERRUPT_SOURCE_SELECTIONS`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InterruptSourceSelection {
+    /// The vector number. Must be in range `128..208` (ICUb) or `128..256`
+    /// (ICUd).
+    pub num: InterruptNum,
+    /// The interrupt source number to write to the vector's selection
+    /// register (`SLIBXRn`, `SLIBRn`, or `SLIARn`). Refer to the device's
+    /// hardware manual for the available interrupt sources.
+    pub source: u8,
+}
+
+/// The instruction set architecture version of a processor core.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CoreVariant {
+    /// RXv1 (e.g., RX62N and RX63N). One 64-bit accumulator (`ACC`).
+    RXv1,
+    /// RXv2 (e.g., RX64M and RX65N). Two 72-bit accumulators (`ACC0` and
+    /// `ACC1`).
+    RXv2,
+    /// RXv3 (e.g., RX66T and RX72N). Two 72-bit accumulators (`ACC0` and
+    /// `ACC1`).
+    RXv3,
+}
+
+/// The flavor of the Interrupt Control Unit (ICU).
+///
+/// All flavors share the layout of the registers used for basic interrupt
+/// control (`IR`, `IER`, `IPR`, `SWINTR`, and `FIR`). The newer flavors add
+/// registers used by optional features.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IcuVariant {
+    /// ICUa (e.g., RX62N).
+    Icua,
+    /// ICUb (e.g., RX63N). Adds group interrupts and software-configurable
+    /// interrupt B.
+    Icub,
+    /// ICUd (e.g., RX64M, RX65N, and RX72N). Adds `SWINT2` (vector 26),
+    /// software-configurable interrupt A, and reorganizes group interrupts.
+    Icud,
+}
+
+/// Specifies whether the double-precision floating-point unit (DFPU) context is
+/// saved and restored by context switching.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DfpuContext {
+    /// The DFPU context is not saved. Only one task can use the DFPU.
+    Disabled,
+    /// The DFPU context is saved for every task.
+    Always,
+    /// The DFPU context is saved only for the tasks that have called
+    /// [`Dfpu::use_dfpu`][], which is mandatory for the tasks using the DFPU.
+    PerTask,
+}
+
 /// The configuration of the port.
 pub trait ThreadingOptions {
+    /// The instruction set architecture version of the target processor.
+    /// Defaults to [`CoreVariant::RXv1`].
+    ///
+    /// This determines the set of registers saved and restored by context
+    /// switching. Note that the assembler must be configured to accept the
+    /// instructions of the specified version.
+    ///
+    /// On RXv1, the lowest 16 bits of the accumulator are not preserved across
+    /// context switches because there's no instruction to read them.
+    const CORE_VARIANT: CoreVariant = CoreVariant::RXv1;
+
+    /// Specifies whether the DFPU context (`DR0`–`DR15`, `DPSW`, `DCMR`,
+    /// `DECNT`, and `DEPC`) is saved and restored by context switching.
+    /// Defaults to [`DfpuContext::Disabled`]. Any other value requires
+    /// [`Self::CORE_VARIANT`]` == `[`CoreVariant::RXv3`].
+    ///
+    /// The DFPU context takes 144 bytes in a task's stack. Interrupt handlers
+    /// don't save the DFPU context, so they must not use the DFPU.
+    const DFPU_CONTEXT: DfpuContext = DfpuContext::Disabled;
+
     /// The priority value to which CPU Lock boosts the processor interrupt
-    /// priority level. Must be in range `0..16`. Defaults to `15` when
+    /// priority level. Must be in range `1..16`. Defaults to `15` when
     /// unspecified.
     ///
-    /// The lower bound of [`MANAGED_INTERRUPT_PRIORITY_RANGE`] is calculated
-    /// using this value as `0..CPU_LOCK_PRIORITY_MASK + 1`.
+    /// [`MANAGED_INTERRUPT_PRIORITY_RANGE`] is calculated using this value as
+    /// `0..CPU_LOCK_PRIORITY_MASK + 1`. Interrupts having a priority higher
+    /// than this value are unmanaged interrupts, which are never masked by the
+    /// kernel. If this is `15`, there are no unmanaged interrupts, and CPU Lock
+    /// is implemented by clearing `PSW.I`.
     ///
     /// [`MANAGED_INTERRUPT_PRIORITY_RANGE`]: r3_kernel::PortInterrupts::MANAGED_INTERRUPT_PRIORITY_RANGE
     const CPU_LOCK_PRIORITY_MASK: u8 = 15;
 
-    /// Enables nested interrupts.
+    /// Enables nested interrupts. Defaults to `false`.
+    ///
+    /// When enabled, an interrupt handler runs with `PSW.IPL` set to the
+    /// priority of the interrupt being handled and can be preempted by an
+    /// interrupt having a higher priority. Dispatch requests made by nested
+    /// interrupt handlers are processed when the outermost interrupt handler
+    /// returns.
     const SUPPORT_NESTING: bool = false;
 
+    /// Specifies whether non-maskable interrupts can be taken and return to
+    /// the interrupted code, e.g., because [`StartupOptions::NMI_HOOK`][] is
+    /// specified. Defaults to `false`.
+    ///
+    /// If this is `false` and [`Self::CPU_LOCK_PRIORITY_MASK`] is `15`, the
+    /// dispatcher borrows the interrupt stack by copying `ISP` to `USP`, which
+    /// a returning non-maskable interrupt handler would corrupt. If this is
+    /// `true`, the dispatcher runs on the interrupt stack directly instead.
+    /// [`use_startup!`][] rejects `StartupOptions::NMI_HOOK` unless this is
+    /// `true`.
+    ///
+    /// [`StartupOptions::NMI_HOOK`]: crate::StartupOptions::NMI_HOOK
+    /// [`use_startup!`]: crate::use_startup
+    const RESUMABLE_NMI: bool = false;
+
     /// Enables the use of the `wait` instruction in the idle task to save power.
     /// Defaults to `true`.
+    ///
+    /// If this is `false`, the idle task executes a busy loop instead. This
+    /// might be useful when an on-chip debugger can't handle the low-power
+    /// state.
     const USE_WAIT: bool = true;
 
+    /// A function to be called repeatedly by the idle task. Defaults to
+    /// `None`.
+    ///
+    /// If [`Self::USE_WAIT`] is `true`, the `wait` instruction is executed
+    /// after each call, so the function is called again every time the
+    /// processor is woken up by an interrupt that doesn't cause a context
+    /// switch.
+    ///
+    /// The function runs in the idle task, which is not a task known to the
+    /// kernel, so it must not call kernel services that require a running
+    /// task, such as blocking operations. It runs on a dedicated stack of
+    /// [`Self::IDLE_HOOK_STACK_SIZE`] bytes, which is discarded whenever the
+    /// idle task is switched away.
+    const IDLE_HOOK: Option<fn()> = None;
+
+    /// The size of the stack used by [`Self::IDLE_HOOK`], measured in bytes.
+    /// Must be a multiple of `4`. Defaults to `256`.
+    ///
+    /// The stack must be large enough to hold the first-level state (40 bytes)
+    /// saved by interrupt handlers in addition to the stack usage of the idle
+    /// hook.
+    const IDLE_HOOK_STACK_SIZE: usize = 256;
+
     /// The base address of the memory-mapped registers exposed by Interrupt
-    /// Control Unit (ICUa or compatible). The default value is `0x0008_7000`.
+    /// Control Unit. The default value is `0x0008_7000`.
     const ICU_BASE: *mut () = 0x0008_7000 as _;
+
+    /// The flavor of the Interrupt Control Unit at [`Self::ICU_BASE`].
+    /// Defaults to [`IcuVariant::Icua`].
+    ///
+    /// Specifying an older flavor than the actual one is safe but disables
+    /// the features that depend on the newer one (e.g., pending `SWINT2` by
+    /// hardware).
+    const ICU_VARIANT: IcuVariant = IcuVariant::Icua;
+
+    /// Maps an interrupt line to the index of the interrupt priority register
+    /// (`ICU.IPR[_]`) that controls it. Defaults to `None`.
+    ///
+    /// This is required to support [`InterruptLine::set_priority`][] and
+    /// [`InterruptLine::define`][]`().priority(_)`. `rsrx` provides a table
+    /// for each supported device (e.g., `rsrx::devices::rx63n::ipr_index`).
+    /// Note that an interrupt priority register may be shared by multiple
+    /// interrupt lines, in which case changing the priority of one line
+    /// affects the others.
+    ///
+    /// [`InterruptLine::set_priority`]: r3_core::kernel::InterruptLine::set_priority
+    /// [`InterruptLine::define`]: r3_core::kernel::InterruptLine::define
+    const INTERRUPT_LINE_IPR_INDEX: Option<fn(InterruptNum) -> Option<usize>> = None;
+
+    /// The group interrupts to be demultiplexed by the port. Defaults to `&[]`.
+    /// Up to [`MAX_GROUP_INTERRUPTS`] group interrupts can be specified.
+    ///
+    /// The port takes over the vector of each group interrupt, which must not
+    /// have an interrupt handler. The interrupt sources of
+    /// `GROUP_INTERRUPTS[i]` are exposed as virtual interrupt lines
+    /// [`group_interrupt_num`]`(i, source)`, which can be used like any other
+    /// interrupt lines with the following differences:
+    ///
+    ///  - Enabling or disabling an interrupt source updates its bit in the
+    ///    group's interrupt request enable register (e.g., `GENBL0`). The
+    ///    group interrupt's vector itself is enabled during boot.
+    ///
+    ///  - All interrupt sources of a group share the priority of the group
+    ///    interrupt's vector, which is specified by
+    ///    [`GroupInterrupt::priority`] and set during boot. Setting the
+    ///    priority of an interrupt source (or the group interrupt's vector)
+    ///    fails with `BadParam` unless the new priority is equal to
+    ///    `GroupInterrupt::priority`. This requires
+    ///    [`Self::INTERRUPT_LINE_IPR_INDEX`].
+    ///
+    ///  - Clearing the pending flag of an interrupt source is only supported
+    ///    for edge-detected groups.
+    ///
+    /// The interrupt sources are checked and their handlers are called in the
+    /// ascending order of bit positions.
+    const GROUP_INTERRUPTS: &'static [GroupInterrupt] = &[];
+
+    /// The interrupt sources to assign to software-configurable interrupt
+    /// vectors. Defaults to `&[]`. Requires [`IcuVariant::Icub`] or
+    /// [`IcuVariant::Icud`].
+    ///
+    /// The selection registers are programmed during boot, before the kernel
+    /// initializes interrupt lines. After that, interrupt handlers attached
+    /// to these vectors receive the events of the selected interrupt sources.
+    /// Interrupt sources that are further divided into units (`PIARn` and
+    /// `PIBRn`) must be configured by the application.
+    const INTERRUPT_SOURCE_SELECTIONS: &'static [InterruptSourceSelection] = &[];
+
+    /// The interrupt line to designate as a fast interrupt. Defaults to
+    /// `None`.
+    ///
+    /// A fast interrupt saves `PC` and `PSW` to the backup registers (`BPC`
+    /// and `BPSW`) instead of the stack, reducing the interrupt response time.
+    /// It's taken with the highest priority (`15`) regardless of the
+    /// interrupt line's `IPR` setting, and its handler runs with all
+    /// interrupts disabled.
+    ///
+    /// The fast interrupt line is excluded from
+    /// [`MANAGED_INTERRUPT_LINES`][], so interrupt handlers attached to it must
+    /// be [unmanaged-safe][].
+    ///
+    /// [`MANAGED_INTERRUPT_LINES`]: r3_kernel::PortInterrupts::MANAGED_INTERRUPT_LINES
+    /// [unmanaged-safe]: r3_core::kernel::StaticInterruptHandlerDefiner::unmanaged
+    const FAST_INTERRUPT_NUM: Option<InterruptNum> = None;
+
+    /// The priority of the software interrupt (`SWINT`, vector 27), which
+    /// the port uses to pend the interrupt lines that can't be pended by
+    /// hardware (see [`InterruptLine::pend`][]). Must be a managed priority
+    /// in range `1..=`[`Self::CPU_LOCK_PRIORITY_MASK`]. Defaults to `1`.
+    ///
+    /// The port enables `SWINT` with this priority during boot. The
+    /// application must not disable `SWINT` or set its priority to `0`;
+    /// otherwise, pending such an interrupt line fails with `NotSupported`.
+    ///
+    /// The interrupt handler of a software-pended interrupt line runs at this
+    /// priority instead of its own. If [`Self::SUPPORT_NESTING`] is `true`,
+    /// this means that it may be preempted by an interrupt having a lower
+    /// priority than the pended interrupt line (a priority inversion), and
+    /// that it may preempt an interrupt handler having a higher priority than
+    /// the pended interrupt line if `SWINT` has a higher priority. The
+    /// default value minimizes the latter.
+    ///
+    /// [`InterruptLine::pend`]: r3_core::kernel::InterruptLine::pend
+    const SWINT_PRIORITY: InterruptPriority = 1;
+
+    /// Called when an interrupt is taken through a vector that has no
+    /// interrupt handler. `num` is the vector number. The default
+    /// implementation panics.
+    ///
+    /// This includes the vectors `0..16`, which are taken by the `int #n` and
+    /// `brk` instructions. For these vectors and the interrupt lines without
+    /// an interrupt handler, this function is called on the interrupt stack
+    /// with interrupts disabled, without entering an interrupt context, so it
+    /// must be [unmanaged-safe][]. For a software-pended interrupt line or a
+    /// virtual interrupt line of a group interrupt, it's called in an
+    /// interrupt context instead.
+    ///
+    /// If this function returns, the port returns to the interrupted context,
+    /// so it should clear the cause of the interrupt (e.g., by disabling the
+    /// interrupt line) to prevent the interrupt from being taken again
+    /// immediately.
+    ///
+    /// [unmanaged-safe]: r3_core::kernel::StaticInterruptHandlerDefiner::unmanaged
+    fn unhandled_interrupt_hook(num: InterruptNum) {
+        panic!("unhandled interrupt {}", num);
+    }
+
+    /// The default stack size for tasks, in bytes. Defaults to `2048`.
+    ///
+    /// [`use_port!`] uses this value for
+    /// [`PortThreading::STACK_DEFAULT_SIZE`][]. It must be a multiple of
+    /// [`Self::STACK_ALIGN`] and satisfy the minimum stack size described in
+    /// [`Self::STACK_MARGIN`].
+    ///
+    /// [`PortThreading::STACK_DEFAULT_SIZE`]: r3_kernel::PortThreading::STACK_DEFAULT_SIZE
+    const STACK_DEFAULT_SIZE: usize = 2048;
+
+    /// The alignment requirement for task stacks, in bytes. Must be a power of
+    /// two not less than `4`. Defaults to `4`.
+    ///
+    /// [`use_port!`] uses this value for [`PortThreading::STACK_ALIGN`][].
+    ///
+    /// [`PortThreading::STACK_ALIGN`]: r3_kernel::PortThreading::STACK_ALIGN
+    const STACK_ALIGN: usize = 4;
+
+    /// The number of bytes that a task's stack must have in addition to the
+    /// space used by the port. Defaults to `0`.
+    ///
+    /// The minimum stack size of a task is the sum of this value, the size of
+    /// the task's initial context state (which depends on
+    /// [`Self::CORE_VARIANT`] and [`Self::DFPU_CONTEXT`]), and the size of the
+    /// stack guard areas (see [`Self::STACK_OVERFLOW_CHECK`] and
+    /// [`Self::MPU_STACK_GUARD_REGION`]). [`use_port!`] exposes it as
+    /// `$Traits::MIN_STACK_SIZE`.
+    ///
+    /// [`Self::STACK_DEFAULT_SIZE`] is checked against the minimum stack size
+    /// at compile time. The stack sizes specified for individual tasks aren't
+    /// visible at compile time, so the port checks them when initializing a
+    /// task's context state and panics if a task's stack is smaller than the
+    /// minimum stack size.
+    const STACK_MARGIN: usize = 0;
+
+    /// Enables software stack overflow detection for tasks. Defaults to
+    /// `false`.
+    ///
+    /// When enabled, the port fills the lowest 16 bytes of each task's stack
+    /// with a guard pattern when the task is activated. Whenever a task is
+    /// switched out, the port checks that the saved stack pointer is above
+    /// the guard area and that the guard pattern is intact, and calls
+    /// [`Self::stack_overflow_hook`] if either check fails.
+    ///
+    /// This is a best-effort mechanism. An overflow is only detected when the
+    /// task is switched out, and an overflow that skips over the guard area
+    /// isn't detected unless it's still in progress at that point.
+    const STACK_OVERFLOW_CHECK: bool = false;
+
+    /// Called when [`Self::STACK_OVERFLOW_CHECK`] detects a stack overflow in
+    /// `task`. The default implementation panics.
+    ///
+    /// This function is called in a dispatcher context (CPU Lock is active)
+    /// on the interrupt stack. It can't return because the task's context
+    /// state is assumed to be corrupted.
+    fn stack_overflow_hook(task: &'static TaskCb<Self>) -> !
+    where
+        Self: KernelTraits,
+    {
+        panic!("stack overflow detected in task {:p}", task);
+    }
+
+    /// The base address of the memory-mapped registers exposed by
+    /// Memory-Protection Unit (MPU). The default value is `0x0008_6400`.
+    const MPU_BASE: *mut () = 0x0008_6400 as _;
+
+    /// The MPU region to use as a stack guard region. Must be in range `0..8`.
+    /// Defaults to `None`, in which case the port doesn't use the MPU.
+    ///
+    /// When specified, the port enables the MPU with a background region
+    /// allowing all accesses during the boot process. On every context
+    /// switch, the specified region is reprogrammed to cover the lowest
+    /// [`Self::MPU_STACK_GUARD_SIZE`] bytes (rounded up to the next 16-byte
+    /// boundary) of the next task's stack and deny all accesses to them. An
+    /// access to the guard region causes an access exception. If the fixed
+    /// vector table is generated by [`use_startup!`][], the exception is
+    /// reported to [`StartupOptions::EXCEPTION_HOOK`][] as
+    /// [`Exception::Access`][], and the hook can find the faulting address in
+    /// `MPDEA`.
+    ///
+    /// The MPU only checks memory accesses made in User mode (`PSW.PM ==
+    /// 1`). Accesses made in Supervisor mode are not checked, so the guard
+    /// region only protects User-mode tasks. For this reason, this option
+    /// requires [`Self::USER_MODE_TASKS`].
+    ///
+    /// [`use_startup!`]: crate::use_startup
+    /// [`StartupOptions::EXCEPTION_HOOK`]: crate::StartupOptions::EXCEPTION_HOOK
+    /// [`Exception::Access`]: crate::Exception::Access
+    const MPU_STACK_GUARD_REGION: Option<usize> = None;
+
+    /// The size of the stack guard region in bytes. Must be a non-zero
+    /// multiple of 16. Defaults to `32`. Only used if
+    /// [`Self::MPU_STACK_GUARD_REGION`] is `Some(_)`.
+    const MPU_STACK_GUARD_SIZE: usize = 32;
+
+    /// Enables User-mode tasks. Defaults to `false`.
+    ///
+    /// When enabled, the tasks selected by [`Self::is_user_mode_task`] run in
+    /// User mode (`PSW.PM == 1`). A User-mode task can't execute privileged
+    /// instructions or modify `PSW.I`, `PSW.IPL`, and `INTB`. The privileged
+    /// operations that the port performs on behalf of a task (CPU Lock and
+    /// dispatching) are routed through a kernel call gate, a software
+    /// interrupt (`int #`[`Self::KERNEL_CALL_INT_NUM`]) handled in Supervisor
+    /// mode.
+    ///
+    /// The kernel and the port access memory-mapped registers (e.g., ICU) and
+    /// kernel objects directly from a task context. User mode itself doesn't
+    /// restrict memory accesses. Use the MPU to protect memory regions from
+    /// User-mode tasks.
+    const USER_MODE_TASKS: bool = false;
+
+    /// Determines whether `task` runs in User mode. The default
+    /// implementation returns `false`. Only used if
+    /// [`Self::USER_MODE_TASKS`] is `true`.
+    ///
+    /// This function is called whenever `task` is activated, and the task
+    /// starts in the selected mode. The result must be the same for every
+    /// activation of a given task. A task can be identified by, e.g., its
+    /// entry point parameter (`task.attr.entry_param`).
+    ///
+    /// This function is called with CPU Lock active.
+    fn is_user_mode_task(task: &'static TaskCb<Self>) -> bool
+    where
+        Self: KernelTraits,
+    {
+        let _ = task;
+        false
+    }
+
+    /// The vector number used by the kernel call gate. Must be in range
+    /// `1..16`. Defaults to `1`. Only used if [`Self::USER_MODE_TASKS`] is
+    /// `true`.
+    const KERNEL_CALL_INT_NUM: u8 = 1;
 }
 
 /// Defines the entry points of a port instantiation. Implemented by
@@ -42,6 +489,23 @@ pub trait ThreadingOptions {
 ///
 /// This trait is not intended to be implemented in any other means.
 pub unsafe trait EntryPoint {
+    /// [`ThreadingOptions::RESUMABLE_NMI`] of the port instance, i.e., the
+    /// code reached from [`Self::start`] tolerates non-maskable interrupt
+    /// handlers that return to the interrupted code.
+    const PORT_RESUMABLE_NMI: bool;
+
+    /// [`ThreadingOptions::ICU_BASE`] of the port instance. [`use_startup!`]
+    /// uses it to configure and acknowledge non-maskable interrupts.
+    ///
+    /// [`use_startup!`]: crate::use_startup
+    const PORT_ICU_BASE: *mut ();
+
+    /// [`ThreadingOptions::ICU_VARIANT`] of the port instance. [`use_startup!`]
+    /// uses it to determine the bit layouts of the NMI registers.
+    ///
+    /// [`use_startup!`]: crate::use_startup
+    const PORT_ICU_VARIANT: IcuVariant;
+
     /// Proceed with the boot process.
     ///
     /// # Safety
@@ -59,8 +523,10 @@ pub unsafe trait EntryPoint {
     ///    requirement for the current stack pointer selection (`PSW.U`).
     ///
     ///  - This function needs a stack space to operate. It can overlap with
-    ///    task stacks. If [`CPU_LOCK_PRIORITY_MASK`][]` != 15`, it can overlap
-    ///    with the interrupt stack.
+    ///    task stacks. If [`CPU_LOCK_PRIORITY_MASK`][]` == 15` and no
+    ///    non-maskable interrupts can return to the boot process, it can
+    ///    overlap with the interrupt stack. Otherwise, unmanaged interrupts
+    ///    can be taken during the boot process.
     ///
     ///  - The processor must be in Supervisor mode (`PSW.PM == 0`).
     ///
@@ -81,6 +547,52 @@ pub unsafe trait Icu {
     ) -> Result<(), SetInterruptGroupPriorityError>;
 }
 
+/// Provides the control of the DFPU context of tasks. Indirectly implemented by
+/// [`use_port!`].
+///
+/// # Safety
+///
+/// This trait is not intended to be implemented in any other means.
+pub unsafe trait Dfpu {
+    /// Make the port save and restore the DFPU context of the current task
+    /// from now on. A task must call this before using the DFPU if
+    /// [`ThreadingOptions::DFPU_CONTEXT`][]` == `[`DfpuContext::PerTask`]. The
+    /// setting is reset when the task is activated again.
+    ///
+    /// The port can't detect a task's use of the DFPU, so a task that uses
+    /// the DFPU without calling this method in `PerTask` mode has its DFPU
+    /// registers corrupted by other tasks. Use [`DfpuContext::Always`] if
+    /// that's a concern.
+    ///
+    /// This method does nothing if `DFPU_CONTEXT` has any other value, so
+    /// calling it is unnecessary in that case.
+    fn use_dfpu() -> Result<(), UseDfpuError>;
+}
+
+/// Error type for [`Dfpu::use_dfpu`][].
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(i8)]
+pub enum UseDfpuError {
+    /// The current context is not a task context.
+    BadContext = ResultCode::BadContext as _,
+}
+
+impl From<UseDfpuError> for ResultCode {
+    #[inline]
+    fn from(x: UseDfpuError) -> Self {
+        match x {
+            UseDfpuError::BadContext => Self::BadContext,
+        }
+    }
+}
+
+impl fmt::Debug for UseDfpuError {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        ResultCode::from(*self).fmt(f)
+    }
+}
+
 /// Error type for [`Icu::set_interrupt_group_priority`][].
 #[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 #[repr(i8)]
@@ -125,6 +637,9 @@ impl fmt::Debug for SetInterruptGroupPriorityError {
 /// and [`EntryPoint`].
 /// **Requires [`ThreadingOptions`][] and [`Timer`][].**
 ///
+/// This macro also defines `$Traits::MIN_STACK_SIZE`, the minimum stack
+/// size of a task in bytes (see [`ThreadingOptions::STACK_MARGIN`]).
+///
 /// This macro doesn't provide an implementation of [`PortTimer`], which you
 /// must supply one through other ways.
 /// See [the crate-level documentation](crate#kernel-timing) for possible
@@ -175,8 +690,19 @@ macro_rules! use_port {
             static INTERRUPTS: $crate::threading::imp::ivt::Table =
                 <$Traits as PortInstance>::IVT;
 
+            #[export_name = "r3_port_rx_IDLE_STACK"]
+            #[used]
+            static mut IDLE_STACK: [u32; <$Traits as PortInstance>::IDLE_STACK_LEN] =
+                [0; <$Traits as PortInstance>::IDLE_STACK_LEN];
+
             unsafe impl PortInstance for $Traits {}
 
+            impl $Traits {
+                /// The minimum stack size of a task in bytes.
+                pub const MIN_STACK_SIZE: usize =
+                    $crate::threading::imp::min_stack_size::<$Traits>();
+            }
+
             // Assume `$Traits: KernelTraits`
             unsafe impl PortThreading for $Traits {
                 type PortTaskState = TaskState;
@@ -184,10 +710,10 @@ macro_rules! use_port {
                 const PORT_TASK_STATE_INIT: Self::PortTaskState =
                     $crate::r3_core::utils::Init::INIT;
 
-                const STACK_DEFAULT_SIZE: usize = 2048;
+                const STACK_DEFAULT_SIZE: usize =
+                    <$Traits as ThreadingOptions>::STACK_DEFAULT_SIZE;
 
-                // FIXME: Couldn't find any description on the stack alignment requirement
-                const STACK_ALIGN: usize = 4;
+                const STACK_ALIGN: usize = <$Traits as ThreadingOptions>::STACK_ALIGN;
 
                 #[inline(always)]
                 unsafe fn dispatch_first_task() -> ! {
@@ -241,8 +767,21 @@ macro_rules! use_port {
             }
 
             unsafe impl PortInterrupts for $Traits {
+                const MANAGED_INTERRUPT_PRIORITY_RANGE: Range<InterruptPriority> =
+                    0..<$Traits as ThreadingOptions>::CPU_LOCK_PRIORITY_MASK as InterruptPriority + 1;
+
                 const MANAGED_INTERRUPT_LINES: &'static [InterruptNum] =
-                    &<$Traits as PortInstance>::ALL_INTERRUPT_LINES;
+                    &$crate::threading::imp::managed_interrupt_lines::<
+                        $Traits,
+                        { <$Traits as PortInstance>::NUM_MANAGED_INTERRUPT_LINES },
+                    >();
+
+                unsafe fn set_interrupt_line_priority(
+                    line: InterruptNum,
+                    priority: InterruptPriority,
+                ) -> Result<(), SetInterruptLinePriorityError> {
+                    PORT_STATE.set_interrupt_line_priority::<Self>(line, priority)
+                }
 
                 unsafe fn enable_interrupt_line(line: InterruptNum) -> Result<(), EnableInterruptLineError> {
                     PORT_STATE.enable_interrupt_line::<Self>(line)
@@ -268,6 +807,11 @@ macro_rules! use_port {
             }
 
             unsafe impl EntryPoint for $Traits {
+                const PORT_RESUMABLE_NMI: bool = <$Traits as ThreadingOptions>::RESUMABLE_NMI;
+                const PORT_ICU_BASE: *mut () = <$Traits as ThreadingOptions>::ICU_BASE;
+                const PORT_ICU_VARIANT: $crate::IcuVariant =
+                    <$Traits as ThreadingOptions>::ICU_VARIANT;
+
                 #[inline]
                 unsafe fn start() -> ! {
                     unsafe { PORT_STATE.port_boot::<$Traits>() }
@@ -278,3 +822,39 @@ macro_rules! use_port {
         const _: () = $crate::threading::imp::validate::<$Traits>();
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const fn group(num: InterruptNum, i: usize) -> GroupInterrupt {
+        GroupInterrupt {
+            num,
+            group: InterruptGroup::Bl(i),
+            priority: 1,
+        }
+    }
+
+    #[test]
+    fn group_index_of_vector_found() {
+        let groups = [group(110, 0), group(111, 1), group(112, 2)];
+        assert_eq!(group_index_of_vector(&groups, 110), Some(0));
+        assert_eq!(group_index_of_vector(&groups, 111), Some(1));
+        assert_eq!(group_index_of_vector(&groups, 112), Some(2));
+    }
+
+    #[test]
+    fn group_index_of_vector_not_found() {
+        let groups = [group(110, 0), group(112, 2)];
+        assert_eq!(group_index_of_vector(&groups, 111), None);
+        assert_eq!(group_index_of_vector(&groups, 16), None);
+        assert_eq!(group_index_of_vector(&[], 110), None);
+    }
+
+    #[test]
+    fn group_index_of_vector_const() {
+        const GROUPS: &[GroupInterrupt] = &[group(106, 0), group(114, 1)];
+        const INDEX: Option<usize> = group_index_of_vector(GROUPS, 114);
+        assert_eq!(INDEX, Some(1));
+    }
+}
@@ -2,21 +2,22 @@ use core::{cell::UnsafeCell, mem::MaybeUninit, slice};
 use r3_core::{
     kernel::{
         traits, ClearInterruptLineError, EnableInterruptLineError, InterruptNum, InterruptPriority,
-        PendInterruptLineError, QueryInterruptLineError,
+        PendInterruptLineError, QueryInterruptLineError, SetInterruptLinePriorityError,
     },
     utils::Init,
 };
 use r3_kernel::{KernelTraits, Port, PortToKernel, System, TaskCb};
 use r3_portkit::pptext::pp_asm;
-use rsrx::icua;
+use rsrx::{icua, icub, icud, mpu};
 use tock_registers::{
     fields::FieldValue,
     interfaces::{ReadWriteable, Readable, Writeable},
 };
 
 use crate::{
-    SetInterruptGroupPriorityError, ThreadingOptions, Timer, INTERRUPT_NUM_RANGE,
-    INTERRUPT_PRIORITY_RANGE,
+    CoreVariant, DfpuContext, IcuVariant, SetInterruptGroupPriorityError, ThreadingOptions, Timer,
+    UseDfpuError, GROUP_INTERRUPT_NUM_RANGE, INTERRUPT_NUM_RANGE, INTERRUPT_PRIORITY_RANGE,
+    MAX_GROUP_INTERRUPTS,
 };
 
 /// Implemented on a kernel trait type by [`use_port!`].
@@ -28,30 +29,181 @@ pub unsafe trait PortInstance:
     KernelTraits + Port<PortTaskState = TaskState> + ThreadingOptions + Timer
 {
     const IVT: ivt::Table = ivt::new_table::<Self>();
-    const ALL_INTERRUPT_LINES: [InterruptNum; 256] = {
-        let mut x = [0; 256];
-        let mut i = 0;
-        while i < 256 {
-            x[i] = i as InterruptNum;
+    /// The number of elements in [`managed_interrupt_lines`].
+    const NUM_MANAGED_INTERRUPT_LINES: usize =
+        256 + Self::GROUP_INTERRUPTS.len() * 32 - Self::FAST_INTERRUPT_NUM.is_some() as usize;
+    /// The length (in words) of the idle hook stack.
+    const IDLE_STACK_LEN: usize = if Self::IDLE_HOOK.is_some() {
+        Self::IDLE_HOOK_STACK_SIZE / 4
+    } else {
+        0
+    };
+}
+
+/// Used by `use_port!`. Lists all interrupt lines, including the virtual
+/// interrupt lines of the configured group interrupts, except for the fast
+/// interrupt line. `LEN` must be equal to
+/// [`PortInstance::NUM_MANAGED_INTERRUPT_LINES`].
+pub const fn managed_interrupt_lines<Traits: PortInstance, const LEN: usize>() -> [InterruptNum; LEN]
+{
+    let mut x = [0; LEN];
+    let mut i = 0;
+    let mut num = 0;
+    while num < 256 + Traits::GROUP_INTERRUPTS.len() * 32 {
+        if !matches!(Traits::FAST_INTERRUPT_NUM, Some(fast) if fast == num) {
+            x[i] = num;
             i += 1;
         }
-        x
-    };
+        num += 1;
+    }
+    assert!(i == LEN);
+    x
 }
 
+mod group;
 pub mod ivt;
 
 trait PortInstanceExt: PortInstance {
+    /// CPU Lock is implemented by `PSW.IPL` rather than `PSW.I`, allowing
+    /// unmanaged interrupts to be taken in any context (Case 2 and Case 4 of
+    /// the processor state tables).
+    const CPU_LOCK_BY_IPL: bool = Self::CPU_LOCK_PRIORITY_MASK != 15;
+
+    /// The dispatcher borrows the interrupt stack by copying `ISP` to `USP`.
+    /// This is only possible if no interrupts that return to the interrupted
+    /// code, including non-maskable ones, can be taken in a dispatcher
+    /// context. Otherwise, the dispatcher uses `ISP` directly.
+    const BORROW_ISP: bool = !Self::CPU_LOCK_BY_IPL && !Self::RESUMABLE_NMI;
+
+    /// An interrupt handler may be preempted by another interrupt handler.
+    const MAY_NEST: bool = Self::SUPPORT_NESTING || Self::CPU_LOCK_BY_IPL;
+
+    /// The processor has two 72-bit accumulators (`ACC0` and `ACC1`) as
+    /// opposed to one 64-bit accumulator (`ACC`).
+    const HAS_ACC1: bool = !matches!(Self::CORE_VARIANT, CoreVariant::RXv1);
+
+    /// The ICU has `SWINT2R` (the second software interrupt).
+    const HAS_SWINT2: bool = matches!(Self::ICU_VARIANT, IcuVariant::Icud);
+
+    /// The length (in words) of the accumulator portion of the second-level
+    /// state.
+    const SLS_ACC_LEN: usize = if Self::HAS_ACC1 { 6 } else { 2 };
+
+    /// The length (in words) of the second-level state.
+    const SLS_LEN: usize = Self::SLS_ACC_LEN + 8;
+
+    /// The minimum size (in bytes) of a task stack. This includes the initial
+    /// context state (the return address, the first-level state, the
+    /// second-level state, and the DFPU context), the stack guard areas, and
+    /// [`ThreadingOptions::STACK_MARGIN`].
+    const MIN_STACK_SIZE: usize = {
+        let dfpu_len = match Self::DFPU_CONTEXT {
+            DfpuContext::Disabled => 0,
+            DfpuContext::Always | DfpuContext::PerTask => DFPU_CONTEXT_LEN,
+        };
+        let mut size = (1 + 10 + Self::SLS_LEN + dfpu_len) * 4 + Self::STACK_MARGIN;
+        if Self::STACK_OVERFLOW_CHECK {
+            size += STACK_GUARD_LEN * 4;
+        }
+        if Self::MPU_STACK_GUARD_REGION.is_some() {
+            // The guard region's start is rounded up to a 16-byte boundary
+            size += Self::MPU_STACK_GUARD_SIZE + 12;
+        }
+        size
+    };
+
+    /// Get the ICU registers shared by all ICU flavors.
     #[inline(always)]
     fn icu() -> &'static icua::Registers {
         unsafe { &*(Self::ICU_BASE as *const icua::Registers) }
     }
+
+    /// Get the ICUb registers. Only valid if [`ThreadingOptions::ICU_VARIANT`]
+    /// is [`IcuVariant::Icub`].
+    #[inline(always)]
+    fn icub() -> &'static icub::Registers {
+        debug_assert!(matches!(Self::ICU_VARIANT, IcuVariant::Icub));
+        unsafe { &*(Self::ICU_BASE as *const icub::Registers) }
+    }
+
+    /// Get the ICUd registers. Only valid if [`ThreadingOptions::ICU_VARIANT`]
+    /// is [`IcuVariant::Icud`].
+    #[inline(always)]
+    fn icud() -> &'static icud::Registers {
+        debug_assert!(matches!(Self::ICU_VARIANT, IcuVariant::Icud));
+        unsafe { &*(Self::ICU_BASE as *const icud::Registers) }
+    }
+
+    #[inline(always)]
+    fn mpu() -> &'static mpu::Registers {
+        unsafe { &*(Self::MPU_BASE as *const mpu::Registers) }
+    }
 }
 impl<T: PortInstance> PortInstanceExt for T {}
 
 /// Software interrupt line (pended by `ICU.SWINTR`)
 const INT_SWINT: InterruptNum = 27;
 
+/// The index of the interrupt priority register of [`INT_SWINT`]. This is
+/// common to all ICU flavors.
+const IPR_SWINT: usize = 3;
+
+/// Software interrupt line 2 (pended by `ICU.SWINT2R`, ICUd only)
+const INT_SWINT2: InterruptNum = 26;
+
+/// The software pending flags of interrupt lines. The interrupt lines that
+/// can't be pended by hardware are pended by setting their flags and pending
+/// [`INT_SWINT`], whose handler calls the handlers of the flagged interrupt
+/// lines (`ivt::dispatch_software_pending`).
+///
+/// The flags are updated by `bset` and `bclr`, which are atomic.
+static mut SOFTWARE_PENDING: [u8; GROUP_INTERRUPT_NUM_RANGE.end / 8] =
+    [0; GROUP_INTERRUPT_NUM_RANGE.end / 8];
+
+/// Set the software pending flag of the specified interrupt line.
+#[inline]
+fn set_software_pending(num: InterruptNum) {
+    unsafe {
+        pp_asm!(
+            "bset {bit}, [{byte}].b",
+            bit = in(reg) num % 8,
+            byte = in(reg) core::ptr::addr_of_mut!(SOFTWARE_PENDING[num / 8]),
+            options(nostack, preserves_flags),
+        )
+    };
+}
+
+/// Clear the software pending flag of the specified interrupt line.
+#[inline]
+fn clear_software_pending(num: InterruptNum) {
+    unsafe {
+        pp_asm!(
+            "bclr {bit}, [{byte}].b",
+            bit = in(reg) num % 8,
+            byte = in(reg) core::ptr::addr_of_mut!(SOFTWARE_PENDING[num / 8]),
+            options(nostack, preserves_flags),
+        )
+    };
+}
+
+/// Get a flag indicating whether the specified interrupt line (which may be
+/// a virtual interrupt line of a group interrupt) is enabled.
+#[inline]
+fn is_interrupt_line_enabled<Traits: PortInstance>(num: InterruptNum) -> bool {
+    if let Some((group, mask)) = group::group_source::<Traits>(num) {
+        (group::group_registers::<Traits>(group.group).gen.get() & mask) != 0
+    } else {
+        (Traits::icu().ier[num / 8].get() & (1 << (num % 8))) != 0
+    }
+}
+
+/// Get the software pending flag of the specified interrupt line.
+#[inline]
+fn is_software_pending(num: InterruptNum) -> bool {
+    let flags = unsafe { core::ptr::addr_of!(SOFTWARE_PENDING[num / 8]).read_volatile() };
+    (flags & (1 << (num % 8))) != 0
+}
+
 /// Stores the value of `Traits::state().running_task_ptr()` so that it can
 /// be accessed in naked functions. This field is actually of type
 /// `*mut Option<&'static TaskCb<Traits>>`.
@@ -62,17 +214,45 @@ static mut RUNNING_TASK_PTR: usize = 0;
 
 static mut DISPATCH_PENDING: bool = false;
 
+/// Indicates whether CPU Lock is active. Only used if
+/// `CPU_LOCK_PRIORITY_MASK != 15`, in which case CPU Lock can't be told apart
+/// from an interrupt context by looking at `PSW`.
+static mut CPU_LOCK_ACTIVE: bool = false;
+
+/// The value of `PSW.IPL` (unshifted) to restore when deactivating CPU Lock.
+/// Only used if `CPU_LOCK_PRIORITY_MASK != 15`.
+static mut CPU_LOCK_SAVED_IPL: u32 = 0;
+
 #[used]
 static mut DUMMY: usize = 0;
 
+/// The operation codes of the kernel call gate, through which a User-mode task
+/// requests the port to perform privileged operations. The operation code is
+/// passed in `r1`.
+mod kernel_call {
+    pub const YIELD_CPU: u32 = 0;
+    pub const EXIT_AND_DISPATCH: u32 = 1;
+    pub const ENTER_CPU_LOCK: u32 = 2;
+    pub const LEAVE_CPU_LOCK: u32 = 3;
+}
+
+/// Called by the kernel call gate when it receives an unknown operation.
+extern "C" fn bad_kernel_call() -> ! {
+    panic!("invalid kernel call");
+}
+
 /// Processor Status Word
 #[allow(dead_code)]
 mod psw {
     /// `PSW.I` - Interrpt enable bit
-    pub const I: u32 = 1 << 16;
+    pub const I: u32 = 1 << I_SHIFT;
+    pub const I_SHIFT: u32 = 16;
     /// `PSW.U` - Stack pointer select bit
     pub const U: u32 = 1 << U_SHIFT;
     pub const U_SHIFT: u32 = 17;
+    /// `PSW.PM` - Processor mode select bit
+    pub const PM: u32 = 1 << PM_SHIFT;
+    pub const PM_SHIFT: u32 = 20;
     /// `PSW.IPL` - Processor interrupt priority level
     pub const IPL_MASK: u32 = 0b1111 << IPL_SHIFT;
     pub const IPL_SHIFT: u32 = 24;
@@ -97,6 +277,22 @@ const TASK_DEFAULT_PSW: u32 = psw::I | psw::U;
 
 const TASK_DEFAULT_FPSW: u32 = 0;
 
+const TASK_DEFAULT_DPSW: u32 = 0;
+
+/// The initial `DECNT` value for a task thread (the reset value).
+const TASK_DEFAULT_DECNT: u32 = 1;
+
+/// The length (in words) of the DFPU context (`DR0`–`DR15`, `DPSW`, `DCMR`,
+/// `DECNT`, and `DEPC`).
+const DFPU_CONTEXT_LEN: usize = 16 * 2 + 4;
+
+/// The length (in words) of the guard area at the bottom of a task stack. Only
+/// used if `STACK_OVERFLOW_CHECK` is enabled.
+const STACK_GUARD_LEN: usize = 4;
+
+/// The value to fill the guard area with.
+const STACK_GUARD_PATTERN: u32 = 0x5a5a_a5a5;
+
 pub struct State {}
 
 impl const Default for State {
@@ -109,6 +305,10 @@ impl const Default for State {
 #[repr(C)]
 pub struct TaskState {
     sp: UnsafeCell<u32>,
+    /// Indicates whether the task's context state includes the DFPU context.
+    /// Only used if `DFPU_CONTEXT == PerTask`. Accessed by naked functions
+    /// at offset `4`.
+    uses_dfpu: UnsafeCell<bool>,
 }
 
 unsafe impl Sync for TaskState {}
@@ -117,6 +317,7 @@ impl Init for TaskState {
     #[allow(clippy::declare_interior_mutable_const)] // it's intentional
     const INIT: Self = Self {
         sp: UnsafeCell::new(0),
+        uses_dfpu: UnsafeCell::new(false),
     };
 }
 
@@ -124,8 +325,19 @@ impl State {
     #[inline(always)]
     pub unsafe fn port_boot<Traits: PortInstance>(&self) -> ! {
         // Update PSW for the boot phase
-        unsafe { pp_asm!("mvtipl #15", options(nomem, preserves_flags)) };
-        unsafe { pp_asm!("clrpsw i", options(nomem, preserves_flags)) };
+        if Traits::CPU_LOCK_BY_IPL {
+            unsafe {
+                pp_asm!(
+                    "mvtipl #{}",
+                    const Traits::CPU_LOCK_PRIORITY_MASK,
+                    options(nomem, preserves_flags),
+                )
+            };
+            unsafe { CPU_LOCK_ACTIVE = true };
+        } else {
+            unsafe { pp_asm!("mvtipl #15", options(nomem, preserves_flags)) };
+            unsafe { pp_asm!("clrpsw i", options(nomem, preserves_flags)) };
+        }
 
         // Set the interrupt vector table base
         unsafe {
@@ -135,13 +347,71 @@ impl State {
             )
         };
 
+        // Enable the MPU. Allow all accesses outside the stack guard region.
+        if let Some(region) = Traits::MPU_STACK_GUARD_REGION {
+            let mpu = Traits::mpu();
+            mpu.mpbac.write(
+                mpu::BackgroundAccessControl::UBAC0::SET
+                    + mpu::BackgroundAccessControl::UBAC1::SET
+                    + mpu::BackgroundAccessControl::UBAC2::SET,
+            );
+            mpu.regions[region].repage.write(mpu::EndPage::V::Invalid);
+            mpu.mpen.write(mpu::Enable::MPEN::Enable);
+        }
+
+        // Configure the fast interrupt
+        if let Some(num) = Traits::FAST_INTERRUPT_NUM {
+            unsafe {
+                pp_asm!(
+                    "mvtc #_{}, fintv",
+                    sym ivt::fast_interrupt_handler::<Traits>,
+                    options(nomem, preserves_flags),
+                )
+            };
+            Traits::icu().fir.write(
+                icua::FastInterrupt::FVCT.val(num as u16) + icua::FastInterrupt::FIEN::Enable,
+            );
+        }
+
+        // Enable `INT_SWINT`, which the port owns to pend interrupt lines by
+        // software, unless it's the fast interrupt
+        if Traits::FAST_INTERRUPT_NUM != Some(INT_SWINT) {
+            Traits::icu().ipr[IPR_SWINT].set(Traits::SWINT_PRIORITY as u8);
+            Traits::icu().ier[INT_SWINT / 8].modify(FieldValue::<u8, _>::new(1, INT_SWINT % 8, 1));
+        }
+
+        // Enable the vectors of the group interrupts with the configured
+        // priorities. The interrupt sources are controlled individually by
+        // `GENxx`.
+        for group in Traits::GROUP_INTERRUPTS {
+            // `validate` ensures `INTERRUPT_LINE_IPR_INDEX.is_some()`
+            let ipr_index = Traits::INTERRUPT_LINE_IPR_INDEX.unwrap()(group.num)
+                .expect("group interrupt vector has no priority register");
+            Traits::icu().ipr[ipr_index].set(group.priority as u8);
+            Traits::icu().ier[group.num / 8].modify(FieldValue::<u8, _>::new(1, group.num % 8, 1));
+        }
+
+        // Assign interrupt sources to the software-configurable interrupt
+        // vectors before the kernel enables any interrupt lines
+        if !Traits::INTERRUPT_SOURCE_SELECTIONS.is_empty() {
+            Self::select_interrupt_sources::<Traits>();
+        }
+
+        // Allow unmanaged interrupts to be taken
+        if Traits::CPU_LOCK_BY_IPL {
+            unsafe { pp_asm!("setpsw i", options(nomem, preserves_flags)) };
+        }
+
         // FIXME: Work-around for DCE not honoring `sym` operands
         //        <https://github.com/rust-lang/rustc_codegen_gcc/issues/157>
         unsafe {
             DUMMY = Self::push_second_level_state_and_dispatch::<Traits> as usize
                 + Self::choose_and_get_next_task::<Traits> as usize
                 + Self::yield_cpu_inner::<Traits> as usize
+                + Self::idle_hook::<Traits> as usize
+                + Self::check_task_stack::<Traits> as usize
                 + Self::fl_handler_stage2::<Traits> as usize
+                + ivt::fast_interrupt_handler::<Traits> as usize
                 + ivt::keep_handlers::<Traits>();
         }
 
@@ -161,24 +431,58 @@ impl State {
 
         unsafe {
             pp_asm!("
-                # Enter a dispatcher context
+                # Enter a dispatcher context. `dispatch` needs stack.
+            .ifne {BORROW_ISP}
+                # Borrow ISP. This is safe because no interrupts can be taken
+                # in a dispatcher context.
                 setpsw u
-
-                # `dispatch` needs stack, so borrow ISP. This is safe because of
-                # [ref:flexible_unmanaged_interrupts].
                 mvfc isp, r0
+            .else
+                # Unmanaged interrupts or non-maskable interrupts can be taken
+                # in a dispatcher context, so we can't borrow ISP. Use ISP
+                # directly instead.
+                clrpsw u
+            .endif
 
                 bra _{push_second_level_state_and_dispatch}.dispatch
                 ",
                 push_second_level_state_and_dispatch =
                     sym Self::push_second_level_state_and_dispatch::<Traits>,
+                BORROW_ISP = const Traits::BORROW_ISP as u32,
                 options(noreturn),
             )
         }
     }
 
+    /// Check if the current task is running in User mode, in which case the
+    /// port must use the kernel call gate to perform privileged operations.
+    #[inline(always)]
+    fn is_user_mode<Traits: PortInstance>(&self) -> bool {
+        Traits::USER_MODE_TASKS && (psw::read() & psw::PM) != 0
+    }
+
+    /// Invoke the kernel call gate ([`Self::kernel_call_handler`]).
+    #[inline(always)]
+    unsafe fn kernel_call<Traits: PortInstance>(&self, op: u32) {
+        unsafe {
+            pp_asm!(
+                "int #{}",
+                const Traits::KERNEL_CALL_INT_NUM,
+                inout("r1") op => _,
+                out("r2") _,
+                out("r3") _,
+                options(preserves_flags),
+            );
+        }
+    }
+
     #[inline(always)]
     pub unsafe fn yield_cpu<Traits: PortInstance>(&'static self) {
+        if self.is_user_mode::<Traits>() {
+            unsafe { self.kernel_call::<Traits>(kernel_call::YIELD_CPU) };
+            return;
+        }
+
         unsafe {
             pp_asm!(
                 "
@@ -220,7 +524,11 @@ impl State {
                 beq 0f
 
                 # Enter a dispatcher context
+            .ifeq {CPU_LOCK_BY_IPL}
                 clrpsw i
+            .else
+                mvtipl #{CPU_LOCK_PRIORITY_MASK}
+            .endif
 
                 # Push the rest of the first level context state.
                 push r14
@@ -243,6 +551,8 @@ impl State {
                 DISPATCH_PENDING = sym DISPATCH_PENDING,
                 push_second_level_state_and_dispatch =
                     sym Self::push_second_level_state_and_dispatch::<Traits>,
+                CPU_LOCK_BY_IPL = const Traits::CPU_LOCK_BY_IPL as u32,
+                CPU_LOCK_PRIORITY_MASK = const Traits::CPU_LOCK_PRIORITY_MASK,
                 options(noreturn),
             );
         }
@@ -253,7 +563,39 @@ impl State {
         // Safety: CPU Lock active
         unsafe { Traits::choose_running_task() };
 
-        unsafe { *Traits::state().running_task_ptr() }
+        let next_task = unsafe { *Traits::state().running_task_ptr() };
+
+        if let Some(region) = Traits::MPU_STACK_GUARD_REGION {
+            Self::update_stack_guard::<Traits>(region, next_task);
+        }
+
+        next_task
+    }
+
+    /// Reprogram the MPU region `region` to cover the guard area of
+    /// `task`'s stack.
+    #[inline]
+    fn update_stack_guard<Traits: PortInstance>(
+        region: usize,
+        task: Option<&'static TaskCb<Traits>>,
+    ) {
+        let region = &Traits::mpu().regions[region];
+        if let Some(task) = task {
+            let stack = task.attr.stack.as_ptr() as *mut u8 as usize;
+            let start = (stack + 15) & !15;
+            let end = start + Traits::MPU_STACK_GUARD_SIZE - 16;
+
+            // We are in Supervisor mode, so the MPU doesn't check our memory
+            // accesses while the region is being updated
+            region
+                .rspage
+                .write(mpu::StartPage::RSPN.val(start as u32 >> 4));
+            region
+                .repage
+                .write(mpu::EndPage::REPN.val(end as u32 >> 4) + mpu::EndPage::V::Valid);
+        } else {
+            region.repage.write(mpu::EndPage::V::Invalid);
+        }
     }
 
     /// Do the following steps:
@@ -273,39 +615,71 @@ impl State {
     /// # Safety
     ///
     ///  - The processor state should be in a dispatcher context.
-    ///  - If the current task is an idle task, SP should point to the
-    ///    first-level state on the current task's stack. Otherwise, SP must be
-    ///    zero.
-    /// - `dispatch:` needs a stack space.
+    ///  - If the current task is not an idle task, SP should point to the
+    ///    first-level state on the current task's stack.
+    /// - `dispatch:` needs a stack space. If `CPU_LOCK_PRIORITY_MASK != 15` or
+    ///   `RESUMABLE_NMI`, it must not be a copy of ISP because unmanaged
+    ///   interrupt handlers or non-maskable interrupt handlers may write to
+    ///   the memory locations beneath ISP at any time.
     ///
     #[naked]
     unsafe extern "C" fn push_second_level_state_and_dispatch<Traits: PortInstance>() -> ! {
         unsafe {
             pp_asm!("
                 # Skip saving the second-level state if the current context
-                # is an idle task. Also, in this case, we don't have a stack,
-                # but `choose_and_get_next_task` needs one. Therefore we borrow
+                # is an idle task. Also, in this case, we don't have a stack
+                # (or we have one, but it's dedicated to the idle hook), but
+                # `choose_and_get_next_task` needs one. Therefore we borrow
                 # the interrupt stack. Otherwise, push the second-level state.
                 #
-                #   if usp == 0:
-                #       <running_task is None>
+                #   r1 = running_task;
+                #   if r1.is_none():
                 #       goto WasIdleTask
                 #   else:
                 #       /* ... */
                 #
-                cmp #0, r0
-                beq 0f
-
-                #   r1 = running_task;
                 mov #_{RUNNING_TASK_PTR}, r1
                 mov [r1], r1
                 mov [r1], r1
+                cmp #0, r1
+                beq 0f
 
                 # Push the second-level context state.
-                # TODO: RXv1 lacks `mvfaclo`
-                # mvfaclo r4
+            .ifeq {HAS_ACC1}
+                # RXv1 lacks `mvfaclo`. `mvfacmi` gives us `ACC[47:16]`, and
+                # `ACC[15:0]` is lost.
                 mvfachi r5
+                mvfacmi r4
+                shll #16, r4
                 pushm r4-r13
+            .else
+                # RXv2 and later have two 72-bit accumulators.
+                mvfacgu a0, r3
+                mvfachi a0, r4
+                mvfaclo a0, r5
+                pushm r3-r13
+                mvfacgu a1, r3
+                mvfachi a1, r4
+                mvfaclo a1, r5
+                pushm r3-r5
+            .endif
+
+            .ifne {DFPU_CONTEXT}
+            .if {DFPU_CONTEXT} == {DFPU_CONTEXT_PER_TASK}
+                # Skip the DFPU context if the task hasn't opted in.
+                #
+                #   if !r1.port_task_state.uses_dfpu:
+                #       goto DfpuContextSaved
+                #
+                movu.b 4[r1], r2
+                cmp #0, r2
+                beq 3f
+            .endif
+                # Push the DFPU context.
+                dpushm.l dpsw-depc
+                dpushm.d dr0-dr15
+            3:      # DfpuContextSaved
+            .endif
 
                 # Store SP to `TaskState`
                 #
@@ -313,13 +687,54 @@ impl State {
                 #
                 mov r0, [r1]
 
+            .ifne {STACK_OVERFLOW_CHECK}
+                # Check the task's stack. The check can't be done on the task
+                # stack, which might have overflowed, so switch to the stack
+                # `dispatch:` would use if the task was an idle task (see
+                # `WasIdleTask` below).
+                #
+                #    <r1 = running_task>
+                #    check_task_stack(r1);
+                #
+            .ifne {BORROW_ISP}
+                mvfc isp, r0
+            .else
+                clrpsw u
+            .endif
+                bsr.a _{check_task_stack}
+            .endif
+
             .global _{push_second_level_state_and_dispatch}.dispatch
             _{push_second_level_state_and_dispatch}.dispatch:
             1:
+            .ifne {CPU_LOCK_BY_IPL}
+                # `PSW.IPL` doesn't tell whether CPU Lock is active, so update
+                # `CPU_LOCK_ACTIVE` on behalf of the kernel.
+                #
+                #   CPU_LOCK_ACTIVE = true;
+                #
+                mov #_{CPU_LOCK_ACTIVE}, r1
+                mov.b #1, [r1]
+            .endif
+
                 # Choose the next task to run. `choose_and_get_next_task`
                 # returns the new value of `running_task`.
                 bsr _{choose_and_get_next_task}
 
+            .ifeq {BORROW_ISP}
+                # We might be running on ISP. Select USP before restoring SP.
+                setpsw u
+            .endif
+            .ifne {CPU_LOCK_BY_IPL}
+                # CPU Lock will be inactive in the next task (or the idle
+                # task).
+                #
+                #   CPU_LOCK_ACTIVE = false;
+                #
+                mov #_{CPU_LOCK_ACTIVE}, r2
+                mov.b #0, [r2]
+            .endif
+
                 # Restore SP from `TaskState`
                 #
                 #    <r1 = running_task>
@@ -332,10 +747,39 @@ impl State {
                 beq 2f
                 mov [r1], r0
 
-                # Pop the second-level context state.
+            .ifne {DFPU_CONTEXT}
+            .if {DFPU_CONTEXT} == {DFPU_CONTEXT_PER_TASK}
+                # Skip the DFPU context if the task hasn't opted in.
+                #
+                #   if !r1.port_task_state.uses_dfpu:
+                #       goto DfpuContextRestored
+                #
+                movu.b 4[r1], r2
+                cmp #0, r2
+                beq 3f
+            .endif
+                # Pop the DFPU context.
+                dpopm.d dr0-dr15
+                dpopm.l dpsw-depc
+            3:      # DfpuContextRestored
+            .endif
+
+                # Pop the second-level context state. Restore the guard bits
+                # last because `mvtachi` might overwrite them.
+            .ifeq {HAS_ACC1}
                 popm r4-r13
                 mvtaclo r4
                 mvtachi r5
+            .else
+                popm r3-r5
+                mvtachi r4, a1
+                mvtaclo r5, a1
+                mvtacgu r3, a1
+                popm r3-r13
+                mvtachi r4, a0
+                mvtaclo r5, a0
+                mvtacgu r3, a0
+            .endif
 
                 # Resume the next task by restoring the first-level state
                 popc fpsw
@@ -344,13 +788,24 @@ impl State {
                 rte
 
             0:      # WasIdleTask
-                # Copy ISP to USP. This is safe because of
-                # [ref:flexible_unmanaged_interrupts].
+            .ifne {BORROW_ISP}
+                # Copy ISP to USP. This is safe because no interrupts can be
+                # taken in a dispatcher context.
                 #
                 #    usp = isp;
                 #    goto {push_second_level_state_and_dispatch}.dispatch;
                 #
                 mvfc isp, r0
+            .else
+                # Unmanaged interrupts or non-maskable interrupts can be taken
+                # in a dispatcher context, so we can't borrow ISP. Use ISP
+                # directly instead.
+                #
+                #    psw.u = 0;
+                #    goto {push_second_level_state_and_dispatch}.dispatch;
+                #
+                clrpsw u
+            .endif
                 bra 1b
 
                 # Although it's never used in the program, export this symbol
@@ -358,31 +813,84 @@ impl State {
                 .global _{push_second_level_state_and_dispatch}.idle_task
             _{push_second_level_state_and_dispatch}.idle_task:
             2:      # IdleTask
+            .ifeq {USE_IDLE_HOOK}
                 # Zero SP
                 mov #0, r0
+            .else
+                # Reset SP to the top of the idle hook stack. Anything left in
+                # the stack by the previous run of the idle task is discarded.
+                mov #(_r3_port_rx_IDLE_STACK + {IDLE_HOOK_STACK_SIZE}), r0
+            .endif
 
-                # Transition to a task context. Note that `wait` automatically
-                # sets `PSW.I`.
+                # Transition to a task context
                 mvtipl #0
+                setpsw i
             0:
-                # TODO: Consider `Traits::USE_WAIT`
+            .ifne {USE_IDLE_HOOK}
+                bsr.a _{idle_hook}
+            .endif
+            .ifne {USE_WAIT}
                 wait
+            .endif
                 bra 0b
             ",
                 choose_and_get_next_task = sym Self::choose_and_get_next_task::<Traits>,
                 push_second_level_state_and_dispatch =
                     sym Self::push_second_level_state_and_dispatch::<Traits>,
+                idle_hook = sym Self::idle_hook::<Traits>,
+                check_task_stack = sym Self::check_task_stack::<Traits>,
                 RUNNING_TASK_PTR = sym RUNNING_TASK_PTR,
+                CPU_LOCK_ACTIVE = sym CPU_LOCK_ACTIVE,
+                CPU_LOCK_BY_IPL = const Traits::CPU_LOCK_BY_IPL as u32,
+                BORROW_ISP = const Traits::BORROW_ISP as u32,
+                STACK_OVERFLOW_CHECK = const Traits::STACK_OVERFLOW_CHECK as u32,
+                USE_WAIT = const Traits::USE_WAIT as u32,
+                HAS_ACC1 = const Traits::HAS_ACC1 as u32,
+                DFPU_CONTEXT = const Traits::DFPU_CONTEXT as u32,
+                DFPU_CONTEXT_PER_TASK = const DfpuContext::PerTask as u32,
+                USE_IDLE_HOOK = const Traits::IDLE_HOOK.is_some() as u32,
+                IDLE_HOOK_STACK_SIZE = const Traits::IDLE_STACK_LEN * 4,
                 options(noreturn),
             );
         }
     }
 
+    /// Check the stack of a task that is being switched out, and call
+    /// [`ThreadingOptions::stack_overflow_hook`] if it has overflowed. Called
+    /// by [`Self::push_second_level_state_and_dispatch`] on the interrupt
+    /// stack.
+    extern "C" fn check_task_stack<Traits: PortInstance>(task: &'static TaskCb<Traits>) {
+        let stack = task.attr.stack.as_ptr();
+        let guard = stack as *mut u32;
+        let guard_end = guard.wrapping_add(STACK_GUARD_LEN);
+        let sp = unsafe { *task.port_task_state.sp.get() } as *mut u32;
+
+        let guard_intact = (0..STACK_GUARD_LEN)
+            .all(|i| unsafe { guard.add(i).read_volatile() } == STACK_GUARD_PATTERN);
+
+        if sp < guard_end || !guard_intact {
+            Traits::stack_overflow_hook(task);
+        }
+    }
+
+    /// Calls [`ThreadingOptions::IDLE_HOOK`]. Called by the idle task.
+    extern "C" fn idle_hook<Traits: PortInstance>() {
+        if let Some(hook) = Traits::IDLE_HOOK {
+            hook();
+        }
+    }
+
     #[inline(always)]
     pub unsafe fn exit_and_dispatch<Traits: PortInstance>(
         &'static self,
         _task: &'static TaskCb<Traits>,
     ) -> ! {
+        if self.is_user_mode::<Traits>() {
+            unsafe { self.kernel_call::<Traits>(kernel_call::EXIT_AND_DISPATCH) };
+            // Safety: The kernel call doesn't return
+            unsafe { core::hint::unreachable_unchecked() };
+        }
+
         unsafe {
             pp_asm!("
                 bra _{push_second_level_state_and_dispatch}.dispatch
@@ -396,12 +904,56 @@ impl State {
 
     #[inline(always)]
     pub unsafe fn enter_cpu_lock<Traits: PortInstance>(&self) {
-        unsafe { pp_asm!("clrpsw i", options(preserves_flags, nostack)) };
+        if self.is_user_mode::<Traits>() {
+            unsafe { self.kernel_call::<Traits>(kernel_call::ENTER_CPU_LOCK) };
+        } else if Traits::CPU_LOCK_BY_IPL {
+            // Remember the current `PSW.IPL`, which depends on the current
+            // context
+            let psw = psw::read();
+            unsafe {
+                pp_asm!(
+                    "mvtipl #{}",
+                    const Traits::CPU_LOCK_PRIORITY_MASK,
+                    options(preserves_flags, nostack),
+                )
+            };
+            unsafe {
+                CPU_LOCK_SAVED_IPL = psw & psw::IPL_MASK;
+                CPU_LOCK_ACTIVE = true;
+            }
+        } else {
+            unsafe { pp_asm!("clrpsw i", options(preserves_flags, nostack)) };
+        }
     }
 
     #[inline(always)]
     pub unsafe fn leave_cpu_lock<Traits: PortInstance>(&'static self) {
-        unsafe { pp_asm!("setpsw i", options(preserves_flags, nostack)) };
+        if self.is_user_mode::<Traits>() {
+            unsafe { self.kernel_call::<Traits>(kernel_call::LEAVE_CPU_LOCK) };
+        } else if Traits::CPU_LOCK_BY_IPL {
+            unsafe { CPU_LOCK_ACTIVE = false };
+
+            // Restore `PSW.IPL`. `mvtipl` only takes an immediate value, so
+            // update the whole `PSW` instead. An interrupt handler taken in the
+            // middle of this sequence restores `PSW` when it returns, so it's
+            // safe to do this non-atomically.
+            unsafe {
+                pp_asm!(
+                    "
+                    mvfc psw, {tmp}
+                    and #{NOT_IPL_MASK}, {tmp}
+                    or {ipl}, {tmp}
+                    mvtc {tmp}, psw
+                    ",
+                    ipl = in(reg) CPU_LOCK_SAVED_IPL,
+                    tmp = out(reg) _,
+                    NOT_IPL_MASK = const !psw::IPL_MASK,
+                    options(nostack),
+                )
+            };
+        } else {
+            unsafe { pp_asm!("setpsw i", options(preserves_flags, nostack)) };
+        }
     }
 
     pub unsafe fn initialize_task_state<Traits: PortInstance>(
@@ -410,7 +962,22 @@ impl State {
     ) {
         let stack = task.attr.stack.as_ptr();
         let mut sp = (stack as *mut u8).wrapping_add(stack.len()) as *mut MaybeUninit<u32>;
-        // TODO: Enforce minimum stack size
+
+        // The stack sizes of individual tasks aren't visible to `validate`, so
+        // this can only be checked here. Don't write the initial context state
+        // below the stack's base.
+        assert!(
+            stack.len() >= Traits::MIN_STACK_SIZE,
+            "task stack is smaller than the minimum stack size"
+        );
+
+        // Paint the guard area
+        if Traits::STACK_OVERFLOW_CHECK {
+            let guard = stack as *mut u32;
+            for i in 0..STACK_GUARD_LEN {
+                unsafe { guard.add(i).write_volatile(STACK_GUARD_PATTERN) };
+            }
+        }
 
         let preload_all = cfg!(feature = "preload-registers");
 
@@ -445,27 +1012,62 @@ impl State {
         // PC: The entry point
         first_level[8] = MaybeUninit::new(task.attr.entry_point as usize as u32);
         // PSW
-        first_level[9] = MaybeUninit::new(TASK_DEFAULT_PSW);
+        first_level[9] = MaybeUninit::new(
+            if Traits::USER_MODE_TASKS && Traits::is_user_mode_task(task) {
+                TASK_DEFAULT_PSW | psw::PM
+            } else {
+                TASK_DEFAULT_PSW
+            },
+        );
 
         // Second-level state (saved and restored only when we are doing context
         // switching)
         let second_level = unsafe {
-            sp = sp.wrapping_sub(10);
-            slice::from_raw_parts_mut(sp, 10)
+            sp = sp.wrapping_sub(Traits::SLS_LEN);
+            slice::from_raw_parts_mut(sp, Traits::SLS_LEN)
         };
 
-        // A0, R6-R13: Uninitialized
+        // ACC0, ACC1 (RXv2 and later), R6-R13: Uninitialized
         if preload_all {
-            second_level[0] = MaybeUninit::new(0xa0a0a0a0);
-            second_level[1] = MaybeUninit::new(0xa1a1a1a1);
-            second_level[2] = MaybeUninit::new(0x06060606);
-            second_level[3] = MaybeUninit::new(0x07070707);
-            second_level[4] = MaybeUninit::new(0x08080808);
-            second_level[5] = MaybeUninit::new(0x09090909);
-            second_level[6] = MaybeUninit::new(0x10101010);
-            second_level[7] = MaybeUninit::new(0x11111111);
-            second_level[8] = MaybeUninit::new(0x12121212);
-            second_level[9] = MaybeUninit::new(0x13131313);
+            let (acc, gprs) = second_level.split_at_mut(Traits::SLS_ACC_LEN);
+            for (i, x) in acc.iter_mut().enumerate() {
+                *x = MaybeUninit::new(0xa0a0a0a0 + 0x01010101 * i as u32);
+            }
+            gprs[0] = MaybeUninit::new(0x06060606);
+            gprs[1] = MaybeUninit::new(0x07070707);
+            gprs[2] = MaybeUninit::new(0x08080808);
+            gprs[3] = MaybeUninit::new(0x09090909);
+            gprs[4] = MaybeUninit::new(0x10101010);
+            gprs[5] = MaybeUninit::new(0x11111111);
+            gprs[6] = MaybeUninit::new(0x12121212);
+            gprs[7] = MaybeUninit::new(0x13131313);
+        }
+
+        // DFPU context
+        match Traits::DFPU_CONTEXT {
+            DfpuContext::Disabled => {}
+            DfpuContext::Always => {
+                let dfpu = unsafe {
+                    sp = sp.wrapping_sub(DFPU_CONTEXT_LEN);
+                    slice::from_raw_parts_mut(sp, DFPU_CONTEXT_LEN)
+                };
+
+                // DR0-DR15: Uninitialized
+                if preload_all {
+                    for (i, x) in dfpu[..32].iter_mut().enumerate() {
+                        *x = MaybeUninit::new(0xd0d0d0d0 + 0x01010101 * (i / 2) as u32);
+                    }
+                }
+                // DPSW, DCMR, DECNT, DEPC
+                dfpu[32] = MaybeUninit::new(TASK_DEFAULT_DPSW);
+                dfpu[33] = MaybeUninit::new(0);
+                dfpu[34] = MaybeUninit::new(TASK_DEFAULT_DECNT);
+                dfpu[35] = MaybeUninit::new(0);
+            }
+            DfpuContext::PerTask => {
+                // The task starts without the DFPU context
+                unsafe { *task.port_task_state.uses_dfpu.get() = false };
+            }
         }
 
         let task_state = &task.port_task_state;
@@ -474,7 +1076,11 @@ impl State {
 
     #[inline(always)]
     pub fn is_cpu_lock_active<Traits: PortInstance>(&self) -> bool {
-        (psw::read() & psw::I) == 0
+        if Traits::CPU_LOCK_BY_IPL {
+            unsafe { CPU_LOCK_ACTIVE }
+        } else {
+            (psw::read() & psw::I) == 0
+        }
     }
 
     pub fn is_task_context<Traits: PortInstance>(&self) -> bool {
@@ -493,6 +1099,130 @@ impl State {
         unsafe { RUNNING_TASK_PTR != 0 }
     }
 
+    /// The kernel call gate. Performs the privileged operation specified by
+    /// `r1` (one of the constants in [`kernel_call`]) on behalf of a User-mode
+    /// task. Clobbers `r1`–`r3`.
+    ///
+    /// # Safety
+    ///
+    /// - `PSW.U == 0` (ISP selected)
+    /// - `PSW.I == 0` (interrupts disabled)
+    /// - `PSW.PM == 0`
+    /// - `saved_pc` == `isp[0]` contains the return target.
+    /// - `saved_psw` == `isp[1]` contains the saved PSW, which indicates a task
+    ///   context.
+    ///
+    #[naked]
+    unsafe extern "C" fn kernel_call_handler<Traits: PortInstance>() -> ! {
+        unsafe {
+            pp_asm!(
+                "
+                cmp #{YIELD_CPU}, r1
+                beq 0f
+                cmp #{EXIT_AND_DISPATCH}, r1
+                beq 1f
+                cmp #{ENTER_CPU_LOCK}, r1
+                beq 2f
+                cmp #{LEAVE_CPU_LOCK}, r1
+                bne 3f
+
+                # LEAVE_CPU_LOCK: Update `saved_psw` to deactivate CPU Lock
+                # when returning to the task.
+                #
+                #   saved_psw.I = 1; /* Case 1 and Case 3 */
+                #
+                #   CPU_LOCK_ACTIVE = false; /* Case 2 and Case 4 */
+                #   saved_psw.IPL = CPU_LOCK_SAVED_IPL;
+                #
+                mov (1 * 4)[r0], r2
+            .ifeq {CPU_LOCK_BY_IPL}
+                bset #{PSW_I_SHIFT}, r2
+            .else
+                mov #_{CPU_LOCK_ACTIVE}, r1
+                mov.b #0, [r1]
+                and #{NOT_IPL_MASK}, r2
+                mov #_{CPU_LOCK_SAVED_IPL}, r1
+                or [r1], r2
+            .endif
+                mov r2, (1 * 4)[r0]
+                rte
+
+            2:      # ENTER_CPU_LOCK: Update `saved_psw` to activate CPU Lock
+                #
+                #   saved_psw.I = 0; /* Case 1 and Case 3 */
+                #
+                #   CPU_LOCK_SAVED_IPL = saved_psw.IPL; /* Case 2 and Case 4 */
+                #   saved_psw.IPL = CPU_LOCK_PRIORITY_MASK;
+                #   CPU_LOCK_ACTIVE = true;
+                #
+                mov (1 * 4)[r0], r2
+            .ifeq {CPU_LOCK_BY_IPL}
+                bclr #{PSW_I_SHIFT}, r2
+            .else
+                mov #_{CPU_LOCK_SAVED_IPL}, r3
+                mov r2, r1
+                and #{IPL_MASK}, r1
+                mov r1, [r3]
+                and #{NOT_IPL_MASK}, r2
+                or #({CPU_LOCK_PRIORITY_MASK} << {PSW_IPL_SHIFT}), r2
+                mov #_{CPU_LOCK_ACTIVE}, r1
+                mov.b #1, [r1]
+            .endif
+                mov r2, (1 * 4)[r0]
+                rte
+
+            0:      # YIELD_CPU: Move `(saved_pc, saved_psw)` to the task stack
+                # to reproduce the stack layout expected by `yield_cpu_inner`,
+                # which will find `saved_psw.U == 1`.
+                #
+                #   (r1, r2) = (saved_pc, saved_psw);
+                #   isp += 2;
+                #   psw.U = 1;
+                #   usp -= 2;
+                #   usp[0..2] = (r1, r2);
+                #
+                popm r1-r2
+                setpsw u
+                push r2
+                push r1
+                bra _{yield_cpu_inner}
+
+            1:      # EXIT_AND_DISPATCH: Discard `(saved_pc, saved_psw)` and
+                # enter a dispatcher context in the same way as
+                # `dispatch_first_task`. CPU Lock is already active.
+                add #8, r0
+            .ifne {BORROW_ISP}
+                setpsw u
+                mvfc isp, r0
+            .endif
+                bra _{push_second_level_state_and_dispatch}.dispatch
+
+            3:      # Unknown operation: Something other than the port executed
+                # the `int` instruction. This is not allowed (see `use_port!`).
+                bra.a _{bad_kernel_call}
+                ",
+                YIELD_CPU = const kernel_call::YIELD_CPU,
+                EXIT_AND_DISPATCH = const kernel_call::EXIT_AND_DISPATCH,
+                ENTER_CPU_LOCK = const kernel_call::ENTER_CPU_LOCK,
+                LEAVE_CPU_LOCK = const kernel_call::LEAVE_CPU_LOCK,
+                bad_kernel_call = sym bad_kernel_call,
+                yield_cpu_inner = sym Self::yield_cpu_inner::<Traits>,
+                push_second_level_state_and_dispatch =
+                    sym Self::push_second_level_state_and_dispatch::<Traits>,
+                CPU_LOCK_ACTIVE = sym CPU_LOCK_ACTIVE,
+                CPU_LOCK_SAVED_IPL = sym CPU_LOCK_SAVED_IPL,
+                CPU_LOCK_BY_IPL = const Traits::CPU_LOCK_BY_IPL as u32,
+                BORROW_ISP = const Traits::BORROW_ISP as u32,
+                CPU_LOCK_PRIORITY_MASK = const Traits::CPU_LOCK_PRIORITY_MASK,
+                PSW_I_SHIFT = const psw::I_SHIFT,
+                PSW_IPL_SHIFT = const psw::IPL_SHIFT,
+                IPL_MASK = const psw::IPL_MASK,
+                NOT_IPL_MASK = const !psw::IPL_MASK,
+                options(noreturn),
+            );
+        }
+    }
+
     /// The first-level, second-stage interrupt handler.
     ///
     /// # Safety
@@ -511,17 +1241,54 @@ impl State {
         unsafe {
             pp_asm!(
                 "
-                # [ref:rx_no_nested_interrupts] implies the background context
-                # is always a task context (`saved_psw.U == 1`).
+            .ifne {CPU_LOCK_BY_IPL}
+                # [tag:rx_unmanaged_interrupt_path] If the current interrupt
+                # has a priority higher than `CPU_LOCK_PRIORITY_MASK`, it's an
+                # unmanaged interrupt, which can be taken in any context,
+                # including a dispatcher context and a context where CPU Lock
+                # is active. Handle it like a nested interrupt, which involves
+                # only the interrupt stack and never causes dispatching.
+                #
+                #   if psw.IPL > CPU_LOCK_PRIORITY_MASK:
+                #       goto NestedInterrupt
+                #
+                push r15
+                mvfc psw, r15
+                shlr #{PSW_IPL_SHIFT}, r15
+                cmp #{CPU_LOCK_PRIORITY_MASK}, r15
+                pop r15
+                bgtu 3f
+            .endif
+
+            .ifne {SUPPORT_NESTING}
+                # If nested interrupts are enabled, the background context
+                # might be an interrupt context. Examine `saved_psw.U` to find
+                # out.
+                #
+                #   if saved_psw.U == 0:
+                #       goto NestedInterrupt
+                #
+                push r15
+                mov (3 * 4)[r0], r15
+                btst #{PSW_U_SHIFT}, r15
+                pop r15
+                beq 3f
+            .endif
+
+                # The background context is a task context (`saved_psw.U ==
+                # 1`).
                 #
                 # Set `PSW.U` to examine `usp` and determine if the background
-                # context is an idle task. If so, skip the stacking of FLS.
+                # context is an idle task without a stack. If so, skip the
+                # stacking of FLS. The idle task running the idle hook has a
+                # nonzero `usp` pointing to `_r3_port_rx_IDLE_STACK`, so its
+                # FLS is saved to the idle hook stack just like a task's.
                 #
                 #   if usp == 0:
-                #       <running_task is None>
+                #       <running_task is None && IDLE_HOOK is None>
                 #       goto FLSSaved
                 #
-                #   <running_task is Some(_)>
+                #   <running_task is Some(_) || IDLE_HOOK is Some(_)>
                 #
                 setpsw u
                 cmp #0, r0
@@ -543,8 +1310,14 @@ impl State {
                 #   isp += 1;
                 #
                 pop r1
-                                            # Enter an interrupt context.
-                                            mvtipl #15
+                                            # Enter an interrupt context. If
+                                            # nested interrupts are enabled,
+                                            # leave `IPL` as set by the
+                                            # hardware (i.e., the priority of
+                                            # the current interrupt).
+            .ifeq {SUPPORT_NESTING}
+                                            mvtipl #{CPU_LOCK_PRIORITY_MASK}
+            .endif
                 mov [r1], r1
                                             setpsw i
 
@@ -555,10 +1328,19 @@ impl State {
                 #
                 jsr r1
 
-                # [ref:rx_no_nested_interrupts] again implies the background
-                # context is always a task context (`saved_psw.U == 1`).
+                # Mask managed interrupts so that a nested interrupt handler
+                # can't set `DISPATCH_PENDING` after we check it. This also
+                # puts us in a dispatcher context.
+            .ifeq {CPU_LOCK_BY_IPL}
+                clrpsw i
+            .else
+                mvtipl #{CPU_LOCK_PRIORITY_MASK}
+            .endif
+
+                # We are unwinding the outermost interrupt, so the background
+                # context is a task context (`saved_psw.U == 1`).
                 #
-                # [ref:flexible_unmanaged_interrupts] implies this is a managed
+                # [ref:rx_unmanaged_interrupt_path] implies this is a managed
                 # interrupt handler, and thus it's always possible that it may
                 # set `DISPATCH_PENDING`.
                 #
@@ -579,10 +1361,11 @@ impl State {
                 popm r1-r2
 
                 # Complete the saved FLS by storing `(saved_pc, saved_psw)`
-                # if the background context is not an idle task.
+                # if the FLS was saved. (This is harmless for the idle task
+                # running the idle hook, whose stack is discarded.)
                 #
                 #   if usp != 0:
-                #       <running_task is Some(_)>
+                #       <running_task is Some(_) || IDLE_HOOK is Some(_)>
                 #       fls.pc = saved_pc;
                 #       fls.psw = saved_psw;
                 #
@@ -593,14 +1376,13 @@ impl State {
                 mov r2, (9 * 4)[r0]
 
             2:
-                # Enter a dispatcher context and jump to
+                # We are now in a dispatcher context. Jump to
                 # `push_second_level_state_and_dispatch`.
-                clrpsw i
                 bra _{push_second_level_state_and_dispatch}
 
             1:      # ReturnToBackgroundContext
-                # Restore the FLS from the task stack if the background context
-                # is not an idle task.
+                # Restore the FLS from the task stack (or the idle hook stack)
+                # if it was saved.
                 setpsw u
                 cmp #0, r0
                 beq 2f
@@ -618,26 +1400,152 @@ impl State {
                 #
                 clrpsw u
                 rte
+
+            .ifne {MAY_NEST}
+            3:      # NestedInterrupt
+                # The background context is an interrupt context, or this is an
+                # unmanaged interrupt. Save the FLS to the interrupt stack.
+                pushm r14-r15
+                pushm r1-r5
+                pushc fpsw
+
+                # Get the second-level interrupt handler.
+                #
+                #   let sl_handler = *isp[8];
+                #
+                mov (8 * 4)[r0], r1
+                mov [r1], r1
+
+                # Call the second-level interrupt handler. `IPL` is already set
+                # to the priority of the current interrupt by the hardware.
+                #
+                #   <interrupt context && CPU Lock inactive>
+                #   sl_handler();
+                #
+                setpsw i
+                jsr r1
+
+                # Restore the FLS and return to the background context. Pended
+                # dispatch requests are handled when unwinding the outermost
+                # interrupt.
+                #
+                #   isp += 9;
+                #   pc = saved_pc;
+                #   psw = saved_psw;
+                #   isp += 2;
+                #
+                clrpsw i
+                popc fpsw
+                popm r1-r5
+                popm r14-r15
+                add #4, r0
+                rte
+            .endif
                 ",
                 DISPATCH_PENDING = sym DISPATCH_PENDING,
                 push_second_level_state_and_dispatch =
                     sym Self::push_second_level_state_and_dispatch::<Traits>,
+                PSW_U_SHIFT = const psw::U_SHIFT,
+                PSW_IPL_SHIFT = const psw::IPL_SHIFT,
+                SUPPORT_NESTING = const Traits::SUPPORT_NESTING as u32,
+                MAY_NEST = const Traits::MAY_NEST as u32,
+                CPU_LOCK_BY_IPL = const Traits::CPU_LOCK_BY_IPL as u32,
+                CPU_LOCK_PRIORITY_MASK = const Traits::CPU_LOCK_PRIORITY_MASK,
                 options(noreturn),
             );
         }
     }
 
+    /// Program the selection registers as specified by
+    /// [`ThreadingOptions::INTERRUPT_SOURCE_SELECTIONS`].
+    fn select_interrupt_sources<Traits: PortInstance>() {
+        let selections = Traits::INTERRUPT_SOURCE_SELECTIONS;
+        match Traits::ICU_VARIANT {
+            IcuVariant::Icua => unreachable!(),
+            IcuVariant::Icub => {
+                let icu = Traits::icub();
+                for sel in selections {
+                    icu.slixr[sel.num - 128].set(sel.source);
+                }
+            }
+            IcuVariant::Icud => {
+                let icu = Traits::icud();
+                icu.slipprcr.write(icud::WriteProtect::WPRC::WriteEnabled);
+                for sel in selections {
+                    icu.slixr[sel.num - 128].set(sel.source);
+                }
+                icu.slipprcr.write(icud::WriteProtect::WPRC::WriteProtected);
+            }
+        }
+
+        // Discard the requests made by the previous interrupt sources
+        for sel in selections {
+            Traits::icu().ir[sel.num].set(0);
+        }
+    }
+
+    pub fn set_interrupt_line_priority<Traits: PortInstance>(
+        &'static self,
+        num: InterruptNum,
+        priority: InterruptPriority,
+    ) -> Result<(), SetInterruptLinePriorityError> {
+        let ipr_index =
+            Traits::INTERRUPT_LINE_IPR_INDEX.ok_or(SetInterruptLinePriorityError::NotSupported)?;
+
+        // The interrupt sources of a group interrupt share the priority of
+        // the group interrupt's vector, which is fixed by the configuration
+        let group = match group::group_source::<Traits>(num) {
+            Some((group, _)) => Some(group),
+            None if INTERRUPT_NUM_RANGE.contains(&num) => {
+                group::group_index_of_vector::<Traits>(num).map(|i| &Traits::GROUP_INTERRUPTS[i])
+            }
+            None => return Err(SetInterruptLinePriorityError::BadParam),
+        };
+        if let Some(group) = group {
+            return if priority == group.priority {
+                Ok(())
+            } else {
+                Err(SetInterruptLinePriorityError::BadParam)
+            };
+        }
+
+        let index = ipr_index(num).ok_or(SetInterruptLinePriorityError::BadParam)?;
+
+        <Traits as super::cfg::Icu>::set_interrupt_group_priority(index, priority).map_err(|e| {
+            match e {
+                SetInterruptGroupPriorityError::BadContext => {
+                    SetInterruptLinePriorityError::BadContext
+                }
+                SetInterruptGroupPriorityError::BadParam => SetInterruptLinePriorityError::BadParam,
+            }
+        })
+    }
+
     #[inline]
     pub fn enable_interrupt_line<Traits: PortInstance>(
         &'static self,
         num: InterruptNum,
     ) -> Result<(), EnableInterruptLineError> {
-        if !INTERRUPT_NUM_RANGE.contains(&num) {
+        if let Some((group, mask)) = group::group_source::<Traits>(num) {
+            let regs = group::group_registers::<Traits>(group.group);
+            regs.gen.set(regs.gen.get() | mask);
+            Ok(())
+        } else if !INTERRUPT_NUM_RANGE.contains(&num) {
             Err(EnableInterruptLineError::BadParam)
         } else {
             Traits::icu().ier[num / 8].modify(FieldValue::<u8, _>::new(1, num % 8, 1));
             Ok(())
+        }?;
+
+        // Deliver the software pending request that was held while the
+        // interrupt line was disabled
+        if is_software_pending(num) {
+            Traits::icu()
+                .swintr
+                .write(icua::SoftwareInterruptActivation::SWINT::SET);
         }
+
+        Ok(())
     }
 
     #[inline]
@@ -645,7 +1553,11 @@ impl State {
         &self,
         num: InterruptNum,
     ) -> Result<(), EnableInterruptLineError> {
-        if !INTERRUPT_NUM_RANGE.contains(&num) {
+        if let Some((group, mask)) = group::group_source::<Traits>(num) {
+            let regs = group::group_registers::<Traits>(group.group);
+            regs.gen.set(regs.gen.get() & !mask);
+            Ok(())
+        } else if !INTERRUPT_NUM_RANGE.contains(&num) {
             Err(EnableInterruptLineError::BadParam)
         } else {
             Traits::icu().ier[num / 8].modify(FieldValue::<u8, _>::new(1, num % 8, 0));
@@ -658,14 +1570,51 @@ impl State {
         &'static self,
         num: InterruptNum,
     ) -> Result<(), PendInterruptLineError> {
-        if num == INT_SWINT {
-            Traits::icu()
-                .swintr
-                .write(icua::SoftwareInterruptActivation::SWINT::SET);
-            Ok(())
-        } else {
-            Err(PendInterruptLineError::BadParam)
+        if !INTERRUPT_NUM_RANGE.contains(&num) && group::group_source::<Traits>(num).is_none() {
+            return Err(PendInterruptLineError::BadParam);
+        }
+
+        if num == INT_SWINT2 && Traits::HAS_SWINT2 {
+            Traits::icud()
+                .swint2r
+                .write(icud::SoftwareInterrupt2Activation::SWINT2::SET);
+            return Ok(());
+        }
+
+        if Traits::FAST_INTERRUPT_NUM == Some(INT_SWINT) {
+            // `INT_SWINT` is handled by `fast_interrupt_handler`, which
+            // doesn't know about the software pending flags
+            return if num == INT_SWINT {
+                Traits::icu()
+                    .swintr
+                    .write(icua::SoftwareInterruptActivation::SWINT::SET);
+                Ok(())
+            } else {
+                Err(PendInterruptLineError::BadParam)
+            };
         }
+
+        if Traits::FAST_INTERRUPT_NUM == Some(num) {
+            // The fast interrupt must not be called from a managed context
+            return Err(PendInterruptLineError::BadParam);
+        }
+
+        // The other interrupt lines are pended by software, which is only
+        // possible if `INT_SWINT` is still enabled with a non-zero priority
+        let icu = Traits::icu();
+        if (icu.ier[INT_SWINT / 8].get() & (1 << (INT_SWINT % 8))) == 0
+            || icu.ipr[IPR_SWINT].get() == 0
+        {
+            return Err(PendInterruptLineError::NotSupported);
+        }
+
+        // `INT_SWINT` is flagged as well so that its handler can tell whether
+        // `INT_SWINT` itself is pended.
+        set_software_pending(num);
+        Traits::icu()
+            .swintr
+            .write(icua::SoftwareInterruptActivation::SWINT::SET);
+        Ok(())
     }
 
     #[inline]
@@ -673,10 +1622,21 @@ impl State {
         &self,
         num: InterruptNum,
     ) -> Result<(), ClearInterruptLineError> {
-        if !INTERRUPT_NUM_RANGE.contains(&num) {
+        if let Some((group, mask)) = group::group_source::<Traits>(num) {
+            // Level-detected interrupt sources can only be cleared by
+            // the peripherals
+            let gcr = group::group_registers::<Traits>(group.group)
+                .gcr
+                .ok_or(ClearInterruptLineError::NotSupported)?;
+            clear_software_pending(num);
+            gcr.set(mask);
+            Ok(())
+        } else if !INTERRUPT_NUM_RANGE.contains(&num) {
             Err(ClearInterruptLineError::BadParam)
         } else {
-            Traits::icu().ir[num].set(1);
+            clear_software_pending(num);
+            // `IR` is cleared by writing `0`
+            Traits::icu().ir[num].set(0);
             Ok(())
         }
     }
@@ -686,11 +1646,14 @@ impl State {
         &self,
         num: InterruptNum,
     ) -> Result<bool, QueryInterruptLineError> {
-        if !INTERRUPT_NUM_RANGE.contains(&num) {
+        if let Some((group, mask)) = group::group_source::<Traits>(num) {
+            let regs = group::group_registers::<Traits>(group.group);
+            Ok((regs.grp.get() & mask) != 0 || is_software_pending(num))
+        } else if !INTERRUPT_NUM_RANGE.contains(&num) {
             Err(QueryInterruptLineError::BadParam)
         } else {
             match Traits::icu().ir[num].get() {
-                0 => Ok(false),
+                0 => Ok(is_software_pending(num)),
                 1 => Ok(true),
                 // Safety: `IR[1..8]` is guaranteed to read as zeros
                 _ => unsafe { core::hint::unreachable_unchecked() },
@@ -699,19 +1662,155 @@ impl State {
     }
 }
 
+/// Used by `use_port!`
+pub const fn min_stack_size<Traits: PortInstance>() -> usize {
+    Traits::MIN_STACK_SIZE
+}
+
 /// Used by `use_port!`
 pub const fn validate<Traits: PortInstance>() {
-    // [tag:flexible_unmanaged_interrupts]
     assert!(
-        Traits::CPU_LOCK_PRIORITY_MASK == 15,
-        "`CPU_LOCK_PRIORITY_MASK` having a value other than `15` is not supported yet"
+        Traits::CPU_LOCK_PRIORITY_MASK > 0 && Traits::CPU_LOCK_PRIORITY_MASK < 16,
+        "`CPU_LOCK_PRIORITY_MASK` must be in range `1..16`"
+    );
+
+    assert!(
+        Traits::SWINT_PRIORITY > 0
+            && Traits::SWINT_PRIORITY <= Traits::CPU_LOCK_PRIORITY_MASK as InterruptPriority,
+        "`SWINT_PRIORITY` must be in range `1..=CPU_LOCK_PRIORITY_MASK`"
+    );
+
+    let stack_align = <Traits as ThreadingOptions>::STACK_ALIGN;
+    let stack_default_size = <Traits as ThreadingOptions>::STACK_DEFAULT_SIZE;
+    assert!(
+        stack_align >= 4 && stack_align.is_power_of_two(),
+        "`STACK_ALIGN` must be a power of two not less than 4"
+    );
+    assert!(
+        stack_default_size % stack_align == 0,
+        "`STACK_DEFAULT_SIZE` must be a multiple of `STACK_ALIGN`"
+    );
+    assert!(
+        stack_default_size >= Traits::MIN_STACK_SIZE,
+        "`STACK_DEFAULT_SIZE` is smaller than the minimum stack size"
     );
 
-    // [tag:rx_no_nested_interrupts]
+    if !matches!(Traits::DFPU_CONTEXT, DfpuContext::Disabled) {
+        assert!(
+            matches!(Traits::CORE_VARIANT, CoreVariant::RXv3),
+            "`DFPU_CONTEXT` requires `CORE_VARIANT == RXv3`"
+        );
+    }
+
+    if Traits::IDLE_HOOK.is_some() {
+        assert!(
+            Traits::IDLE_HOOK_STACK_SIZE % 4 == 0,
+            "`IDLE_HOOK_STACK_SIZE` must be a multiple of 4"
+        );
+        // An interrupt handler may push the first-level state to the stack
+        assert!(
+            Traits::IDLE_HOOK_STACK_SIZE >= 4 * 10,
+            "`IDLE_HOOK_STACK_SIZE` is too small to hold the first-level state"
+        );
+    }
+
+    if let Some(num) = Traits::FAST_INTERRUPT_NUM {
+        assert!(
+            num >= INTERRUPT_NUM_RANGE.start && num < INTERRUPT_NUM_RANGE.end,
+            "`FAST_INTERRUPT_NUM` is out of range"
+        );
+    }
+
+    let groups = Traits::GROUP_INTERRUPTS;
     assert!(
-        !Traits::SUPPORT_NESTING,
-        "nested interrupts aren't supported yet"
+        groups.len() <= MAX_GROUP_INTERRUPTS,
+        "too many group interrupts in `GROUP_INTERRUPTS`"
     );
+    assert!(
+        groups.is_empty() || Traits::INTERRUPT_LINE_IPR_INDEX.is_some(),
+        "`GROUP_INTERRUPTS` requires `INTERRUPT_LINE_IPR_INDEX`"
+    );
+    let mut i = 0;
+    while i < groups.len() {
+        let num = groups[i].num;
+        assert!(
+            num >= INTERRUPT_NUM_RANGE.start && num < INTERRUPT_NUM_RANGE.end,
+            "group interrupt vector is out of range"
+        );
+        assert!(
+            !matches!(Traits::FAST_INTERRUPT_NUM, Some(x) if x == num),
+            "group interrupt vector can't be the fast interrupt"
+        );
+        assert!(
+            matches!(group::group_index_of_vector::<Traits>(num), Some(x) if x == i),
+            "duplicate group interrupt vector in `GROUP_INTERRUPTS`"
+        );
+        assert!(
+            Traits::INTERRUPT_HANDLERS.get(num).is_none(),
+            "group interrupt vector must not have an interrupt handler"
+        );
+        assert!(
+            group::is_valid_group::<Traits>(groups[i].group),
+            "group interrupt is not supported by `ICU_VARIANT`"
+        );
+        assert!(
+            groups[i].priority > 0 && groups[i].priority < INTERRUPT_PRIORITY_RANGE.end,
+            "group interrupt priority must be in range `1..16`"
+        );
+        i += 1;
+    }
+
+    let selections = Traits::INTERRUPT_SOURCE_SELECTIONS;
+    let selectable_end = match Traits::ICU_VARIANT {
+        IcuVariant::Icua => 128,
+        IcuVariant::Icub => 208,
+        IcuVariant::Icud => 256,
+    };
+    let mut i = 0;
+    while i < selections.len() {
+        let num = selections[i].num;
+        assert!(
+            !matches!(Traits::ICU_VARIANT, IcuVariant::Icua),
+            "`INTERRUPT_SOURCE_SELECTIONS` is not supported by `ICU_VARIANT`"
+        );
+        assert!(
+            num >= 128 && num < selectable_end,
+            "`INTERRUPT_SOURCE_SELECTIONS` contains a vector that isn't \
+            software-configurable"
+        );
+        let mut k = 0;
+        while k < i {
+            assert!(
+                selections[k].num != num,
+                "duplicate vector in `INTERRUPT_SOURCE_SELECTIONS`"
+            );
+            k += 1;
+        }
+        i += 1;
+    }
+
+    if Traits::USER_MODE_TASKS {
+        assert!(
+            Traits::KERNEL_CALL_INT_NUM > 0
+                && (Traits::KERNEL_CALL_INT_NUM as InterruptNum) < INTERRUPT_NUM_RANGE.start,
+            "`KERNEL_CALL_INT_NUM` must be in range `1..16`"
+        );
+    }
+
+    if let Some(region) = Traits::MPU_STACK_GUARD_REGION {
+        assert!(
+            Traits::USER_MODE_TASKS,
+            "`MPU_STACK_GUARD_REGION` requires `USER_MODE_TASKS`"
+        );
+        assert!(
+            region < 8,
+            "`MPU_STACK_GUARD_REGION` must be in range `0..8`"
+        );
+        assert!(
+            Traits::MPU_STACK_GUARD_SIZE > 0 && Traits::MPU_STACK_GUARD_SIZE % 16 == 0,
+            "`MPU_STACK_GUARD_SIZE` must be a non-zero multiple of 16"
+        );
+    }
 }
 
 unsafe impl<Traits: PortInstance> super::cfg::Icu for Traits {
@@ -737,3 +1836,25 @@ unsafe impl<Traits: PortInstance> super::cfg::Icu for Traits {
         Ok(())
     }
 }
+
+unsafe impl<Traits: PortInstance> super::cfg::Dfpu for Traits {
+    fn use_dfpu() -> Result<(), UseDfpuError> {
+        if !matches!(Traits::DFPU_CONTEXT, DfpuContext::PerTask) {
+            return Ok(());
+        }
+
+        if !Traits::is_task_context() {
+            return Err(UseDfpuError::BadContext);
+        }
+
+        // Safety: The running task can't change while it's running
+        let task = unsafe { *Traits::state().running_task_ptr() };
+        let task = task.ok_or(UseDfpuError::BadContext)?;
+
+        // Safety: `uses_dfpu` is only accessed by the task itself and the
+        // dispatcher, which doesn't run while the task is running
+        unsafe { *task.port_task_state.uses_dfpu.get() = true };
+
+        Ok(())
+    }
+}
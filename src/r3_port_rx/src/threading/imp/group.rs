@@ -0,0 +1,121 @@
+//! Group interrupt demultiplexing
+use r3_core::kernel::InterruptNum;
+use tock_registers::{
+    interfaces::{Readable, Writeable},
+    registers::{ReadOnly, ReadWrite},
+};
+
+use super::{PortInstance, PortInstanceExt};
+use crate::threading::cfg::{GroupInterrupt, InterruptGroup, GROUP_INTERRUPT_NUM_RANGE};
+
+/// The registers controlling a group interrupt.
+pub(super) struct GroupRegisters {
+    /// The interrupt request register (e.g., `GRPBL0`)
+    pub grp: &'static ReadOnly<u32>,
+    /// The interrupt request enable register (e.g., `GENBL0`)
+    pub gen: &'static ReadWrite<u32>,
+    /// The interrupt clear register (e.g., `GCRBE0`). Only present in
+    /// edge-detected groups.
+    pub gcr: Option<&'static ReadWrite<u32>>,
+}
+
+pub(super) fn group_registers<Traits: PortInstance>(group: InterruptGroup) -> GroupRegisters {
+    match group {
+        InterruptGroup::IcubEdge(i) => {
+            let icu = Traits::icub();
+            GroupRegisters {
+                grp: &icu.grp[i],
+                gen: &icu.gen[i],
+                gcr: Some(&icu.gcr[i]),
+            }
+        }
+        InterruptGroup::IcubLevel(i) => {
+            let icu = Traits::icub();
+            GroupRegisters {
+                grp: &icu.grp[i],
+                gen: &icu.gen[i],
+                gcr: None,
+            }
+        }
+        InterruptGroup::Be0 => {
+            let icu = Traits::icud();
+            GroupRegisters {
+                grp: &icu.grpbe0,
+                gen: &icu.genbe0,
+                gcr: Some(&icu.gcrbe0),
+            }
+        }
+        InterruptGroup::Bl(i) => {
+            let icu = Traits::icud();
+            GroupRegisters {
+                grp: &icu.grpbl[i],
+                gen: &icu.genbl[i],
+                gcr: None,
+            }
+        }
+        InterruptGroup::Al(i) => {
+            let icu = Traits::icud();
+            GroupRegisters {
+                grp: &icu.grpal[i],
+                gen: &icu.genal[i],
+                gcr: None,
+            }
+        }
+    }
+}
+
+/// Decode a virtual interrupt line number. Returns the group interrupt and
+/// the bit mask of the interrupt source, or `None` if `num` doesn't refer to
+/// an interrupt source of a configured group interrupt.
+#[inline]
+pub(super) fn group_source<Traits: PortInstance>(
+    num: InterruptNum,
+) -> Option<(&'static GroupInterrupt, u32)> {
+    let i = num.checked_sub(GROUP_INTERRUPT_NUM_RANGE.start)?;
+    let group = Traits::GROUP_INTERRUPTS.get(i / 32)?;
+    Some((group, 1 << (i % 32)))
+}
+
+/// Get the index of the group interrupt (in
+/// [`ThreadingOptions::GROUP_INTERRUPTS`]) that uses the specified vector.
+///
+/// [`ThreadingOptions::GROUP_INTERRUPTS`]: crate::ThreadingOptions::GROUP_INTERRUPTS
+pub(super) const fn group_index_of_vector<Traits: PortInstance>(
+    num: InterruptNum,
+) -> Option<usize> {
+    crate::threading::cfg::group_index_of_vector(Traits::GROUP_INTERRUPTS, num)
+}
+
+/// Get a flag indicating whether the specified `InterruptGroup` is supported
+/// by the configured ICU flavor and refers to an existing group.
+pub(super) const fn is_valid_group<Traits: PortInstance>(group: InterruptGroup) -> bool {
+    use crate::threading::cfg::IcuVariant;
+    match (Traits::ICU_VARIANT, group) {
+        (IcuVariant::Icub, InterruptGroup::IcubEdge(i) | InterruptGroup::IcubLevel(i)) => i < 13,
+        (IcuVariant::Icud, InterruptGroup::Be0) => true,
+        (IcuVariant::Icud, InterruptGroup::Bl(i)) => i < 3,
+        (IcuVariant::Icud, InterruptGroup::Al(i)) => i < 2,
+        _ => false,
+    }
+}
+
+/// The second-level interrupt handler of the group interrupt
+/// `GROUP_INTERRUPTS[group_index]`. Calls the interrupt handlers of the
+/// group's interrupt sources that are pending and enabled.
+pub(super) unsafe fn dispatch_group<Traits: PortInstance>(group_index: usize) {
+    let regs = group_registers::<Traits>(Traits::GROUP_INTERRUPTS[group_index].group);
+
+    let mut pending = regs.grp.get() & regs.gen.get();
+    while pending != 0 {
+        let source = pending.trailing_zeros() as usize;
+        pending &= pending - 1;
+
+        // An edge-detected interrupt source remains pending until cleared
+        if let Some(gcr) = regs.gcr {
+            gcr.set(1 << source);
+        }
+
+        let num = GROUP_INTERRUPT_NUM_RANGE.start + group_index * 32 + source;
+        unsafe { super::ivt::call_line_handler::<Traits>(num) };
+    }
+}
@@ -1,21 +1,62 @@
 //! Interrupt vector table and interrupt handler generation
-use super::{PortInstance, State};
+use r3_core::kernel::InterruptNum;
+
+use super::{
+    clear_software_pending, group, is_interrupt_line_enabled, PortInstance, State, INT_SWINT,
+    SOFTWARE_PENDING,
+};
+use crate::threading::cfg::{GROUP_INTERRUPT_NUM_RANGE, INTERRUPT_NUM_RANGE};
 
 pub type Table = [unsafe extern "C" fn() -> !; 256];
 
 /// Generate the interrupt vector table for the specified system trait type.
+///
+/// A vector that has no interrupt handler gets a small first-level handler
+/// that only records the vector number and jumps to [`unhandled_stage2`], so
+/// that [`ThreadingOptions::unhandled_interrupt_hook`] can be told the vector
+/// number. This includes the vectors `0..16`, which are only taken by
+/// software traps (`int #n` and `brk`) and must not enter the managed
+/// interrupt path.
+///
+/// [`ThreadingOptions::unhandled_interrupt_hook`]: crate::ThreadingOptions::unhandled_interrupt_hook
 pub(super) const fn new_table<Traits: PortInstance>() -> Table {
     seq_macro::seq!(I in 0..256 {
         [ #(
-            if Traits::INTERRUPT_HANDLERS.get(I).is_some() {
+            if is_kernel_call_gate::<Traits>(I) {
+                State::kernel_call_handler::<Traits>
+            } else if is_handled::<Traits>(I) {
                 fl_handler_stage1::<Traits, I>
             } else {
-                unhandled_interrupt
+                unhandled_stage1::<Traits, I>
             },
         )* ]
     })
 }
 
+/// Get a flag indicating whether the specified vector is handled by the
+/// managed interrupt path ([`fl_handler_stage1`]).
+const fn is_handled<Traits: PortInstance>(num: usize) -> bool {
+    INTERRUPT_NUM_RANGE.start <= num
+        && num < INTERRUPT_NUM_RANGE.end
+        && !is_fast_interrupt::<Traits>(num)
+        && (num == INT_SWINT
+            || group::group_index_of_vector::<Traits>(num).is_some()
+            || Traits::INTERRUPT_HANDLERS.get(num).is_some())
+}
+
+/// Get a flag indicating whether the specified interrupt line is designated
+/// as a fast interrupt. A fast interrupt is dispatched through `FINTV`, so it
+/// doesn't need an entry in the relocatable vector table.
+const fn is_fast_interrupt<Traits: PortInstance>(num: usize) -> bool {
+    matches!(Traits::FAST_INTERRUPT_NUM, Some(x) if x == num)
+}
+
+/// Get a flag indicating whether the specified vector is used by the kernel
+/// call gate.
+const fn is_kernel_call_gate<Traits: PortInstance>(num: usize) -> bool {
+    Traits::USER_MODE_TASKS && num == Traits::KERNEL_CALL_INT_NUM as usize
+}
+
 /// FIXME: Work-around for DCE not honoring `sym` operands
 /// <https://github.com/rust-lang/rustc_codegen_gcc/issues/157>
 #[inline]
@@ -29,7 +70,7 @@ pub(super) fn keep_handlers<Traits: PortInstance>() -> usize {
         // FIXME: `StaticVec: !~const Destruct`
         let mut fns = ManuallyDrop::new(fns);
         seq_macro::seq!(I in 0..256 {
-            if Traits::INTERRUPT_HANDLERS.get(I).is_some() {
+            if is_handled::<Traits>(I) {
                 fns.push(sl_handler_trampoline::<Traits, I>);
             }
         });
@@ -58,15 +99,179 @@ unsafe extern "C" fn fl_handler_stage1<Traits: PortInstance, const I: usize>() -
 }
 
 unsafe extern "C" fn sl_handler_trampoline<Traits: PortInstance, const I: usize>() {
-    // I hoped that this would be devirtualized and inlined, but neither
-    // did happen
-    //     unsafe { Traits::INTERRUPT_HANDLERS.get(I).unwrap()() }
+    if I == INT_SWINT {
+        unsafe { dispatch_software_pending::<Traits>() };
+    } else if let Some(group_index) = const { group::group_index_of_vector::<Traits>(I) } {
+        unsafe { group::dispatch_group::<Traits>(group_index) };
+    } else {
+        // I hoped that this would be devirtualized and inlined, but neither
+        // did happen
+        //     unsafe { Traits::INTERRUPT_HANDLERS.get(I).unwrap()() }
+
+        if let Some(handler) = const { Traits::INTERRUPT_HANDLERS.get(I) } {
+            unsafe { handler() }
+        } else {
+            Traits::unhandled_interrupt_hook(I);
+        }
+    }
+}
+
+/// Call the interrupt handler of the specified interrupt line, which may be
+/// a virtual interrupt line of a group interrupt. Used when the interrupt
+/// line isn't known at compile time.
+pub(super) unsafe fn call_line_handler<Traits: PortInstance>(num: InterruptNum) {
+    if let Some(group_index) = group::group_index_of_vector::<Traits>(num) {
+        unsafe { group::dispatch_group::<Traits>(group_index) };
+    } else if let Some(handler) = Traits::INTERRUPT_HANDLERS.get(num) {
+        unsafe { handler() };
+    } else {
+        Traits::unhandled_interrupt_hook(num);
+    }
+}
+
+/// The second-level interrupt handler of [`INT_SWINT`]. Calls the interrupt
+/// handlers of the interrupt lines pended by software (including
+/// `INT_SWINT` itself).
+///
+/// The interrupt handlers are called at the priority of `INT_SWINT`. The
+/// requests of disabled interrupt lines are left pending and delivered by
+/// `enable_interrupt_line`.
+unsafe fn dispatch_software_pending<Traits: PortInstance>() {
+    for i in 0..GROUP_INTERRUPT_NUM_RANGE.end / 8 {
+        let flags = unsafe { core::ptr::addr_of!(SOFTWARE_PENDING[i]).read_volatile() };
+        if flags == 0 {
+            continue;
+        }
+
+        for bit in 0..8 {
+            let num = i * 8 + bit;
+            if (flags & (1 << bit)) != 0 && is_interrupt_line_enabled::<Traits>(num) {
+                clear_software_pending(num);
+                unsafe { call_line_handler::<Traits>(num) };
+            }
+        }
+    }
+}
+
+/// The first-level interrupt handler of a vector that has no interrupt
+/// handler. Calls [`unhandled_stage2`] with the vector number stored after
+/// the call instruction.
+#[naked]
+unsafe extern "C" fn unhandled_stage1<Traits: PortInstance, const I: usize>() -> ! {
+    unsafe {
+        core::arch::asm!(
+            "
+            bsr.a _{unhandled_stage2}
+
+            # `unhandled_stage2` will find this via the last return address
+            .byte {I}
+            ",
+            unhandled_stage2 = sym unhandled_stage2::<Traits>,
+            I = const I,
+            options(noreturn),
+        );
+    }
+}
+
+/// The handler shared by all vectors that have no interrupt handler. Calls
+/// [`ThreadingOptions::unhandled_interrupt_hook`] without entering an
+/// interrupt context and returns to the interrupted context.
+///
+/// [`ThreadingOptions::unhandled_interrupt_hook`]: crate::ThreadingOptions::unhandled_interrupt_hook
+///
+/// # Safety
+///
+/// - `PSW.U == 0` (ISP selected)
+/// - `PSW.I == 0` (interrupts disabled)
+/// - `PSW.PM == 0`
+/// - `isp[0]` points to the vector number (a byte).
+/// - `isp[1]` contains the return target.
+/// - `isp[2]` contains the saved PSW.
+///
+#[naked]
+unsafe extern "C" fn unhandled_stage2<Traits: PortInstance>() -> ! {
+    unsafe {
+        core::arch::asm!(
+            "
+            # Save the FLS to the interrupt stack.
+            pushm r14-r15
+            pushm r1-r5
+            pushc fpsw
+
+            # Get the vector number.
+            #
+            #   let num = *isp[8] as u8;
+            #
+            mov (8 * 4)[r0], r1
+            movu.b [r1], r1
+
+            bsr.a _{call_hook}
+
+            # Restore the FLS and return to the background context, discarding
+            # the pointer to the vector number.
+            popc fpsw
+            popm r1-r5
+            popm r14-r15
+            add #4, r0
+            rte
+            ",
+            call_hook = sym call_unhandled_interrupt_hook::<Traits>,
+            options(noreturn),
+        );
+    }
+}
 
-    extern "C" fn noop() {}
-    const NOOP: unsafe extern "C" fn() = noop;
-    unsafe { const { Traits::INTERRUPT_HANDLERS.get(I).unwrap_or(NOOP) }() }
+extern "C" fn call_unhandled_interrupt_hook<Traits: PortInstance>(num: usize) {
+    Traits::unhandled_interrupt_hook(num);
 }
 
-unsafe extern "C" fn unhandled_interrupt() -> ! {
-    panic!("unhandled interrupt")
+/// The fast interrupt handler, which `FINTV` points to.
+///
+/// # Safety
+///
+/// - `PSW.U == 0` (ISP selected)
+/// - `PSW.I == 0` (interrupts disabled)
+/// - `PSW.PM == 0`
+/// - `BPC` and `BPSW` contain the return target and the saved PSW.
+///
+#[naked]
+#[repr(align(4))]
+pub(super) unsafe extern "C" fn fast_interrupt_handler<Traits: PortInstance>() -> ! {
+    unsafe {
+        core::arch::asm!(
+            "
+            # Save the FLS to the interrupt stack. `(pc, psw)` is already saved
+            # to `(bpc, bpsw)` by the hardware.
+            pushm r14-r15
+            pushm r1-r5
+            pushc fpsw
+
+            # Call the second-level interrupt handler. Leave interrupts
+            # disabled; another fast interrupt would overwrite `(bpc, bpsw)`.
+            bsr.a _{sl_handler}
+
+            # Restore the FLS and return to the background context.
+            popc fpsw
+            popm r1-r5
+            popm r14-r15
+            rtfi
+            ",
+            sl_handler = sym fast_sl_handler_trampoline::<Traits>,
+            options(noreturn),
+        );
+    }
+}
+
+unsafe extern "C" fn fast_sl_handler_trampoline<Traits: PortInstance>() {
+    let handler = const {
+        match Traits::FAST_INTERRUPT_NUM {
+            Some(i) => Traits::INTERRUPT_HANDLERS.get(i),
+            None => None,
+        }
+    };
+    if let Some(handler) = handler {
+        unsafe { handler() }
+    } else if let Some(num) = Traits::FAST_INTERRUPT_NUM {
+        Traits::unhandled_interrupt_hook(num);
+    }
 }
@@ -0,0 +1,156 @@
+//! The public interface for the 8-Bit Timer (TMR) timer driver.
+use r3_core::kernel::{InterruptNum, InterruptPriority};
+
+/// Attach the implementation of [`PortTimer`] based on 8-Bit Timer (TMR) to a
+/// given kernel trait type. This macro also implements [`Timer`] on the kernel
+/// trait type.
+/// **Requires [`TmrOptions`].**
+///
+/// [`PortTimer`]: r3_kernel::PortTimer
+/// [`Timer`]: crate::Timer
+///
+/// This driver cascades the two channels of a TMR unit to form a free-running
+/// 16-bit counter and uses the compare match A interrupt (`CMIAn` of the
+/// even-numbered channel) to generate interrupts. This is useful if all CMT
+/// channels are used by the application.
+///
+/// You should do the following:
+///
+///  - Implement [`TmrOptions`] on the kernel trait type `$Traits`.
+///  - Call `$Traits::configure_timer()` in your configuration function.
+///    See the following example.
+///
+/// ```rust,ignore
+/// r3_port_rx::use_tmr!(unsafe impl PortTimer for SystemTraits);
+///
+/// impl r3_port_rx::TmrOptions for SystemTraits {
+///     const FREQUENCY: u64 = 48_000_000;
+///     const PREDIVIDER: u64 = 32;
+///     // TMR0 CMIA0 (RX63N)
+///     const INTERRUPT_NUM: InterruptNum = 170;
+///     const IPR_INDEX: Option<usize> = Some(170);
+/// }
+///
+/// const fn configure_app(b: &mut r3_kernel::Cfg<SystemTraits>) -> Objects {
+///     SystemTraits::configure_timer(b);
+///     /* ... */
+/// }
+/// ```
+///
+/// # Safety
+///
+///  - `TmrOptions` must be configured correctly.
+///
+#[macro_export]
+macro_rules! use_tmr {
+    (unsafe impl PortTimer for $Traits:ty) => {
+        const _: () = {
+            use $crate::r3_core::{
+                kernel::{traits, Cfg},
+                utils::Init,
+            };
+            use $crate::r3_kernel::{PortTimer, System, UTicks};
+            use $crate::r3_portkit::tickless;
+            use $crate::{tmr, Timer, TmrOptions};
+
+            impl PortTimer for $Traits {
+                const MAX_TICK_COUNT: UTicks = u32::MAX;
+                const MAX_TIMEOUT: UTicks = u32::MAX;
+
+                unsafe fn tick_count() -> UTicks {
+                    // Safety: We are just forwarding the call
+                    unsafe { tmr::imp::tick_count::<Self>() }
+                }
+
+                unsafe fn pend_tick() {
+                    // Safety: We are just forwarding the call
+                    unsafe { tmr::imp::pend_tick::<Self>() }
+                }
+
+                unsafe fn pend_tick_after(tick_count_delta: UTicks) {
+                    // Safety: We are just forwarding the call
+                    unsafe { tmr::imp::pend_tick_after::<Self>(tick_count_delta) }
+                }
+            }
+
+            impl Timer for $Traits {
+                unsafe fn init() {
+                    unsafe { tmr::imp::init::<Self>() }
+                }
+            }
+
+            static mut TIMER_STATE: tmr::imp::TimerState<
+                <$Traits as tmr::imp::TimerInstance>::TicklessState,
+            > = Init::INIT;
+
+            // Safety: Only `use_tmr!` is allowed to `impl` this
+            unsafe impl tmr::imp::TimerInstance for $Traits {
+                type TicklessState = tickless::TicklessState<{ Self::TICKLESS_CFG }>;
+
+                fn timer_state() -> *mut tmr::imp::TimerState<Self::TicklessState> {
+                    unsafe { core::ptr::addr_of_mut!(TIMER_STATE) }
+                }
+            }
+
+            impl $Traits {
+                pub const fn configure_timer<C>(b: &mut Cfg<C>)
+                where
+                    C: ~const traits::CfgBase<System = System<Self>>
+                        + ~const traits::CfgInterruptLine,
+                {
+                    tmr::imp::configure(b);
+                }
+            }
+        };
+    };
+}
+
+/// The options for [`use_tmr!`].
+pub trait TmrOptions {
+    /// The base address of the memory-mapped registers exposed by an 8-Bit
+    /// Timer unit (a channel pair). Defaults to `0x0008_8200` (TMR0/1) when
+    /// unspecified. Use `0x0008_8210` for TMR2/3.
+    const TMR_BASE: *mut () = 0x0008_8200 as _;
+
+    /// The numerator of the effective input clock rate (usually PCLK) of the
+    /// timer unit. This will be further divided by [`Self::PREDIVIDER`] to
+    /// determine the actual timer clock.
+    const FREQUENCY: u64;
+
+    /// The denominator of the effective input clock rate (usually PCLK) of the
+    /// timer unit. Defaults to `1`.
+    const FREQUENCY_DENOMINATOR: u64 = 1;
+
+    /// Set the divider ratio for the predivider (`TCCR.CKS`). Must be one of
+    /// `[1, 2, 8, 32, 64, 1024, 8192]`.
+    const PREDIVIDER: u64;
+
+    /// The maximum permissible timer interrupt latency, measured in hardware
+    /// timer cycles.
+    ///
+    /// Defaults to `min(FREQUENCY / FREQUENCY_DENOMINATOR / PREDIVIDER / 100,
+    /// 0x8000)` (10 milliseconds maximum).
+    const HEADROOM: u16 = {
+        let x = Self::FREQUENCY as u128
+            / Self::FREQUENCY_DENOMINATOR as u128
+            / Self::PREDIVIDER as u128
+            / 100;
+        if x < 0x8000 {
+            x as u16
+        } else {
+            0x8000
+        }
+    };
+
+    /// The interrupt number of the compare match A interrupt of the
+    /// even-numbered channel (`CMIAn`). This differs between devices, e.g.,
+    /// `174` (RX62N) or `170` (RX63N) for TMR0.
+    const INTERRUPT_NUM: InterruptNum;
+
+    /// The IPR register used to set the priority of [`Self::INTERRUPT_NUM`],
+    /// e.g., `Some(0x68)` (RX62N) or `Some(170)` (RX63N) for TMR0.
+    const IPR_INDEX: Option<usize>;
+
+    /// The interrupt priority. Defaults to `4`.
+    const INTERRUPT_PRIORITY: InterruptPriority = 4;
+}
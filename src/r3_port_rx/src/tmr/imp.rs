@@ -0,0 +1,295 @@
+//! The implementation of the timer driver based on 8-Bit Timer (TMR).
+use r3_core::{
+    kernel::{traits, Cfg, InterruptLine, StartupHook, StaticInterruptHandler},
+    utils::Init,
+};
+use r3_kernel::{KernelTraits, PortToKernel, System, UTicks};
+use r3_portkit::tickless::{TicklessCfg, TicklessOptions, TicklessStateTrait};
+use rsrx::tmr;
+use tock_registers::{
+    fields::FieldValue,
+    interfaces::{Readable, Writeable},
+};
+
+use crate::{cmt::cfg::current_hw_tick_count32, tmr::cfg::TmrOptions, Icu, INTERRUPT_NUM_RANGE};
+
+/// Implemented on a kernel trait type by [`use_tmr!`].
+///
+/// # Safety
+///
+/// Only meant to be implemented by [`use_tmr!`].
+pub unsafe trait TimerInstance: KernelTraits + TmrOptions + Icu {
+    const TICKLESS_CFG: TicklessCfg = match TicklessCfg::new(TicklessOptions {
+        hw_freq_num: <Self as TmrOptions>::FREQUENCY,
+        hw_freq_denom: <Self as TmrOptions>::FREQUENCY_DENOMINATOR
+            .checked_mul(<Self as TmrOptions>::PREDIVIDER)
+            .expect("frequency denominator overflowed"),
+        hw_headroom_ticks: <Self as TmrOptions>::HEADROOM as u32,
+        force_full_hw_period: true,
+        resettable: true,
+    }) {
+        Ok(x) => x,
+        Err(e) => e.panic(),
+    };
+
+    type TicklessState: TicklessStateTrait;
+
+    fn timer_state() -> *mut TimerState<Self::TicklessState>;
+
+    const TMR_COUNTER_CONTROL_CKS: FieldValue<u8, tmr::CounterControl::Register> =
+        match <Self as TmrOptions>::PREDIVIDER {
+            1 => tmr::CounterControl::CKS::Pclk,
+            2 => tmr::CounterControl::CKS::PclkDividedBy2,
+            8 => tmr::CounterControl::CKS::PclkDividedBy8,
+            32 => tmr::CounterControl::CKS::PclkDividedBy32,
+            64 => tmr::CounterControl::CKS::PclkDividedBy64,
+            1024 => tmr::CounterControl::CKS::PclkDividedBy1024,
+            8192 => tmr::CounterControl::CKS::PclkDividedBy8192,
+            _ => panic!("`PREDIVIDER` must be one of `[1, 2, 8, 32, 64, 1024, 8192]`"),
+        };
+}
+
+trait TimerInstanceExt: TimerInstance {
+    #[inline(always)]
+    fn tmr() -> &'static tmr::Registers {
+        // Safety: Verified by the user of `use_tmr!`
+        unsafe { &*(Self::TMR_BASE as *const tmr::Registers) }
+    }
+
+    /// Read the cascaded 16-bit counter.
+    #[inline]
+    fn tcnt16() -> u16 {
+        let tmr = Self::tmr();
+        // The lower half may overflow between the reads. Retry until we get a
+        // consistent pair.
+        let mut hi = tmr.tcnt[0].get();
+        loop {
+            let lo = tmr.tcnt[1].get();
+            let hi2 = tmr.tcnt[0].get();
+            if hi == hi2 {
+                return u16::from_le_bytes([lo, hi]);
+            }
+            hi = hi2;
+        }
+    }
+
+    /// Write the cascaded 16-bit time constant register A.
+    #[inline]
+    fn set_tcora16(value: u16) {
+        let tmr = Self::tmr();
+        let [lo, hi] = value.to_le_bytes();
+        tmr.tcora[0].set(hi);
+        tmr.tcora[1].set(lo);
+    }
+}
+impl<T: TimerInstance> TimerInstanceExt for T {}
+
+pub struct TimerState<TicklessState> {
+    tickless_state: TicklessState,
+    /// The last known value of the simulated 32-bit counter. The current value
+    /// can be calculated using `hw_tick_count32: u32` and the 16-bit counter,
+    /// provided that the timer hasn't advanced by more than 2¹⁶ cycles since
+    /// the previous update of `hw_tick_count32`.
+    hw_tick_count32: u32,
+    /// For how many cycles should we wait before `timer_tick` should be called?
+    hw_tick_remaining: u32,
+}
+
+impl<TicklessState: Init> Init for TimerState<TicklessState> {
+    const INIT: Self = Self {
+        tickless_state: Init::INIT,
+        hw_tick_count32: 0,
+        hw_tick_remaining: 0,
+    };
+}
+
+/// The configuration function.
+pub const fn configure<C, Traits: TimerInstance>(b: &mut Cfg<C>)
+where
+    C: ~const traits::CfgBase<System = System<Traits>> + ~const traits::CfgInterruptLine,
+{
+    assert!(
+        Traits::INTERRUPT_NUM >= INTERRUPT_NUM_RANGE.start
+            && Traits::INTERRUPT_NUM < INTERRUPT_NUM_RANGE.end,
+        "`INTERRUPT_NUM` must be in range `16..256`"
+    );
+
+    InterruptLine::define()
+        .line(Traits::INTERRUPT_NUM)
+        .enabled(true)
+        .finish(b);
+    StaticInterruptHandler::define()
+        .line(Traits::INTERRUPT_NUM)
+        .start(handle_tick::<Traits>)
+        .finish(b);
+
+    if <Traits as TmrOptions>::IPR_INDEX.is_some() {
+        StartupHook::define()
+            .start(|| {
+                Traits::set_interrupt_group_priority(
+                    <Traits as TmrOptions>::IPR_INDEX.unwrap(),
+                    <Traits as TmrOptions>::INTERRUPT_PRIORITY,
+                )
+                .unwrap()
+            })
+            .finish(b);
+    }
+}
+
+/// Implements [`crate::Timer::init`]
+#[inline]
+pub fn init<Traits: TimerInstance>() {
+    let tmr = Traits::tmr();
+
+    // Stop the counters
+    tmr.tccr[0].set(0);
+    tmr.tccr[1].set(0);
+
+    // Even-numbered channel (upper 8 bits): Compare match A interrupt enabled
+    // Odd-numbered channel (lower 8 bits): Interrupts disabled
+    // The 16-bit counter is free-running (period = 2¹⁶).
+    tmr.tcr[0].write(tmr::Control::CCLR::NotCleared + tmr::Control::CMIEA::SET);
+    tmr.tcr[1].write(tmr::Control::CCLR::NotCleared);
+    tmr.tcsr[0].write(tmr::ControlStatus::OSA::NoChange + tmr::ControlStatus::OSB::NoChange);
+    tmr.tcsr[1].write(tmr::ControlStatus::OSA::NoChange + tmr::ControlStatus::OSB::NoChange);
+    tmr.tcnt[0].set(0);
+    tmr.tcnt[1].set(0);
+    Traits::set_tcora16(u16::MAX);
+
+    // Start the counters. The even-numbered channel counts the overflows of
+    // the odd-numbered channel.
+    tmr.tccr[0].write(tmr::CounterControl::CSS::OddChannelOverflow);
+    tmr.tccr[1].write(tmr::CounterControl::CSS::InternalClock + Traits::TMR_COUNTER_CONTROL_CKS);
+}
+
+/// Implements [`r3_kernel::PortTimer::tick_count`]
+///
+/// # Safety
+///
+/// Only meant to be referenced by `use_tmr!`.
+pub unsafe fn tick_count<Traits: TimerInstance>() -> UTicks {
+    // Safety: CPU Lock protects it from concurrent access
+    let tstate = unsafe { &mut *Traits::timer_state() };
+
+    // Calculate the value of the simulated 32-bit counter
+    let cur_hw_tick_count32 = current_hw_tick_count32(tstate.hw_tick_count32, Traits::tcnt16());
+
+    let tcfg = &Traits::TICKLESS_CFG;
+    tstate.tickless_state.tick_count(tcfg, cur_hw_tick_count32)
+}
+
+/// Implements [`r3_kernel::PortTimer::pend_tick`]
+///
+/// # Safety
+///
+/// Only meant to be referenced by `use_tmr!`.
+pub unsafe fn pend_tick<Traits: TimerInstance>() {
+    // Safety: CPU Lock protects it from concurrent access
+    let tstate = unsafe { &mut *Traits::timer_state() };
+
+    // Do `timer_tick` on the next interrupt
+    tstate.hw_tick_remaining = 0;
+
+    // Generate an interrupt as soon as possible
+    set_compare::<Traits>(Traits::tcnt16(), 1);
+}
+
+/// Implements [`r3_kernel::PortTimer::pend_tick_after`]
+///
+/// # Safety
+///
+/// Only meant to be referenced by `use_tmr!`.
+pub unsafe fn pend_tick_after<Traits: TimerInstance>(tick_count_delta: UTicks) {
+    // Safety: CPU Lock protects it from concurrent access
+    let tstate = unsafe { &mut *Traits::timer_state() };
+
+    // Update the reference time of the simulated 32-bit counter
+    let cur_hw_tick_count16 = Traits::tcnt16();
+    let cur_hw_tick_count32 = current_hw_tick_count32(tstate.hw_tick_count32, cur_hw_tick_count16);
+    tstate.hw_tick_count32 = cur_hw_tick_count32;
+
+    let tcfg = &Traits::TICKLESS_CFG;
+    let hw_ticks = tstate
+        .tickless_state
+        .mark_reference_and_measure(tcfg, cur_hw_tick_count32, tick_count_delta)
+        .hw_ticks;
+
+    tstate.hw_tick_remaining = hw_ticks;
+
+    set_compare::<Traits>(
+        cur_hw_tick_count16,
+        hw_ticks.clamp(1, max_interval::<Traits>()),
+    );
+}
+
+/// The maximum interval between timer interrupts. We use the timer
+/// interrupts to maintain the simulated 32-bit counter, so the interrupt
+/// period must be maintained under 2¹⁶ cycles, or we'll lose track of it.
+#[inline]
+fn max_interval<Traits: TimerInstance>() -> u32 {
+    (u16::MAX - <Traits as TmrOptions>::HEADROOM).max(1) as u32
+}
+
+/// Configure the time constant register A to generate an interrupt when the
+/// 16-bit counter reaches `start + hw_ticks`. `hw_ticks` must be in range
+/// `1..=u16::MAX`.
+///
+/// A compare match only happens when the counter equals the time constant
+/// register, so if the counter passes the new value while we are updating it,
+/// no interrupt will be generated until the counter wraps around. Detect this
+/// case and retry with a later deadline.
+fn set_compare<Traits: TimerInstance>(mut start: u16, mut hw_ticks: u32) {
+    debug_assert!((1..=u16::MAX as u32).contains(&hw_ticks));
+
+    // Updating the register one byte at a time may cause a spurious compare
+    // match. This is harmless because `handle_tick` checks the elapsed time.
+    let _ = InterruptLine::<System<Traits>>::from_num(Traits::INTERRUPT_NUM).clear();
+
+    let mut margin = 2;
+    loop {
+        Traits::set_tcora16(start.wrapping_add(hw_ticks as u16));
+
+        let now = Traits::tcnt16();
+        if (now.wrapping_sub(start) as u32) < hw_ticks {
+            break;
+        }
+
+        // Missed the deadline. Increase the margin each time so that this
+        // loop terminates even if the timer clock is fast relative to the
+        // processor.
+        start = now;
+        hw_ticks = margin;
+        margin = (margin * 2).min(u16::MAX as u32);
+    }
+}
+
+#[inline]
+fn handle_tick<Traits: TimerInstance>() {
+    // Safety: CPU Lock protects it from concurrent access
+    let tstate = unsafe { &mut *Traits::timer_state() };
+
+    // Update the reference time of the simulated 32-bit counter
+    let cur_hw_tick_count16 = Traits::tcnt16();
+    let cur_hw_tick_count32 = current_hw_tick_count32(tstate.hw_tick_count32, cur_hw_tick_count16);
+    tstate.hw_tick_remaining = tstate
+        .hw_tick_remaining
+        .saturating_sub(cur_hw_tick_count32.wrapping_sub(tstate.hw_tick_count32));
+    tstate.hw_tick_count32 = cur_hw_tick_count32;
+
+    if tstate.hw_tick_remaining == 0 {
+        let tcfg = &Traits::TICKLESS_CFG;
+        tstate
+            .tickless_state
+            .mark_reference(tcfg, cur_hw_tick_count32);
+
+        // Safety: CPU Lock inactive, an interrupt context
+        unsafe { Traits::timer_tick() };
+    } else {
+        // Unlike CMT, the counter isn't cleared by a compare match, so we
+        // have to schedule the next interrupt ourselves
+        set_compare::<Traits>(
+            cur_hw_tick_count16,
+            tstate.hw_tick_remaining.min(max_interval::<Traits>()),
+        );
+    }
+}
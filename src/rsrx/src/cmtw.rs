@@ -0,0 +1,116 @@
+//! Compare Match Timer W
+//!
+//! CMTW is a 32-bit timer found in RXv2 and later devices (e.g., RX64M and
+//! RX65N). Each unit has one channel, which provides a compare match register
+//! (`CMWCOR`), two input capture registers, and two output compare registers.
+use tock_registers::{
+    register_bitfields, register_structs,
+    registers::{ReadOnly, ReadWrite},
+};
+
+register_structs! {
+    /// The memory-mapped registers exposed by Compare Match Timer W.
+    pub Registers {
+        /// Timer start register
+        (0x00 => pub cmwstr: ReadWrite<u16, Start::Register>),
+        (0x02 => _pad0),
+        /// Timer control register
+        (0x04 => pub cmwcr: ReadWrite<u16, Control::Register>),
+        (0x06 => _pad1),
+        /// Timer I/O control register
+        (0x08 => pub cmwior: ReadWrite<u16, IoControl::Register>),
+        (0x0a => _pad2),
+        /// Timer counter
+        (0x10 => pub cmwcnt: ReadWrite<u32>),
+        /// Compare match constant register
+        (0x14 => pub cmwcor: ReadWrite<u32>),
+        /// Input capture register 0
+        (0x18 => pub cmwicr0: ReadOnly<u32>),
+        /// Input capture register 1
+        (0x1c => pub cmwicr1: ReadOnly<u32>),
+        /// Output compare register 0
+        (0x20 => pub cmwocr0: ReadWrite<u32>),
+        /// Output compare register 1
+        (0x24 => pub cmwocr1: ReadWrite<u32>),
+        (0x28 => @END),
+    }
+}
+
+register_bitfields![u16,
+    pub Start [
+        /// Counter start
+        STR OFFSET(0) NUMBITS(1) [
+            Stop = 0,
+            Start = 1,
+        ],
+    ],
+
+    pub Control [
+        /// Clock select
+        CKS OFFSET(0) NUMBITS(2) [
+            PclkDividedBy8 = 0,
+            PclkDividedBy32 = 1,
+            PclkDividedBy128 = 2,
+            PclkDividedBy512 = 3,
+        ],
+        /// Compare match interrupt enable
+        CMWIE OFFSET(3) NUMBITS(1) [],
+        /// Input capture 0 interrupt enable
+        IC0IE OFFSET(4) NUMBITS(1) [],
+        /// Input capture 1 interrupt enable
+        IC1IE OFFSET(5) NUMBITS(1) [],
+        /// Output compare 0 interrupt enable
+        OC0IE OFFSET(6) NUMBITS(1) [],
+        /// Output compare 1 interrupt enable
+        OC1IE OFFSET(7) NUMBITS(1) [],
+        /// Timer counter size
+        CMS OFFSET(9) NUMBITS(1) [
+            Bits32 = 0,
+            Bits16 = 1,
+        ],
+        /// Counter clear
+        CCLR OFFSET(13) NUMBITS(3) [
+            ClearedByCompareMatch = 0b000,
+            FreeRunning = 0b001,
+            ClearedByInputCapture0 = 0b100,
+            ClearedByInputCapture1 = 0b101,
+        ],
+    ],
+
+    pub IoControl [
+        /// Input capture control 0
+        IC0 OFFSET(0) NUMBITS(2) [
+            RisingEdge = 0b00,
+            FallingEdge = 0b01,
+            BothEdges = 0b10,
+        ],
+        /// Input capture control 1
+        IC1 OFFSET(2) NUMBITS(2) [
+            RisingEdge = 0b00,
+            FallingEdge = 0b01,
+            BothEdges = 0b10,
+        ],
+        /// Input capture 0 enable
+        IC0E OFFSET(4) NUMBITS(1) [],
+        /// Input capture 1 enable
+        IC1E OFFSET(5) NUMBITS(1) [],
+        /// Output compare control 0
+        OC0 OFFSET(8) NUMBITS(2) [
+            Retain = 0b00,
+            InitialLowToggle = 0b01,
+            InitialHighToggle = 0b10,
+        ],
+        /// Output compare control 1
+        OC1 OFFSET(10) NUMBITS(2) [
+            Retain = 0b00,
+            InitialLowToggle = 0b01,
+            InitialHighToggle = 0b10,
+        ],
+        /// Output compare 0 enable
+        OC0E OFFSET(12) NUMBITS(1) [],
+        /// Output compare 1 enable
+        OC1E OFFSET(13) NUMBITS(1) [],
+        /// Compare match enable
+        CMWE OFFSET(15) NUMBITS(1) [],
+    ],
+];
@@ -6,15 +6,18 @@ use tock_registers::{
     registers::{ReadOnly, ReadWrite},
 };
 
-use crate::{cmt, icua, scia};
+use crate::{cmt, icua, mpu, scia, tmr};
 
 peripheral_set! {
     /// All the peripherals of RX62N
     pub struct Peripherals {
         pub SYSTEM: SYSTEM,
+        pub MPU: MPU,
         pub ICU: ICU,
         pub CMT0_1: CMT0_1,
         pub CMT2_3: CMT2_3,
+        pub TMR0_1: TMR0_1,
+        pub TMR2_3: TMR2_3,
         pub SCI0: SCI0,
         pub SCI1: SCI1,
         pub SCI2: SCI2,
@@ -28,9 +31,12 @@ peripheral_set! {
 }
 
 zero_sized_ref!(pub struct SYSTEM: &system::Registers = 0x0008_0000);
+zero_sized_ref!(pub struct MPU: &mpu::Registers = 0x0008_6400);
 zero_sized_ref!(pub struct ICU: &icua::Registers = 0x0008_7000);
 zero_sized_ref!(pub struct CMT0_1: &cmt::Registers = 0x0008_8000);
 zero_sized_ref!(pub struct CMT2_3: &cmt::Registers = 0x0008_8010);
+zero_sized_ref!(pub struct TMR0_1: &tmr::Registers = 0x0008_8200);
+zero_sized_ref!(pub struct TMR2_3: &tmr::Registers = 0x0008_8210);
 zero_sized_ref!(pub struct SCI0: &scia::Registers = 0x0008_8240);
 zero_sized_ref!(pub struct SCI1: &scia::Registers = 0x0008_8248);
 zero_sized_ref!(pub struct SCI2: &scia::Registers = 0x0008_8250);
@@ -41,6 +47,45 @@ zero_sized_ref!(pub struct SCI6: &scia::Registers = 0x0008_8270);
 zero_sized_ref!(pub struct PORTS: &ports::Registers = 0x0008_C000);
 zero_sized_ref!(pub struct IOPORT: &ioport::Registers = 0x0008_C100);
 
+/// Get the index of the interrupt priority register (`ICU.IPR[_]`) that
+/// controls the priority of the specified interrupt vector.
+///
+/// Returns `None` if the vector is reserved, doesn't have a priority
+/// register, or isn't listed here yet.
+pub const fn ipr_index(vector: usize) -> Option<usize> {
+    Some(match vector {
+        // BSC BUSERR
+        16 => 0x00,
+        // FCU FIFERR
+        21 => 0x01,
+        // FCU FRDYI
+        23 => 0x02,
+        // ICU SWINT
+        27 => 0x03,
+        // CMT0..3 CMIn
+        28..=31 => 0x04 + (vector - 28),
+        // ETHER EINT
+        32 => 0x08,
+        // ICU IRQ0..15
+        64..=79 => 0x20 + (vector - 64),
+        // WDT WOVI
+        96 => 0x40,
+        // AD0 ADI0, AD1 ADI1
+        98..=99 => 0x44 + (vector - 98),
+        // S12AD ADI
+        102 => 0x48,
+        // TMR0..3 CMIAn, CMIBn, OVIn
+        174..=185 => 0x68 + (vector - 174) / 3,
+        // DMAC DMAC0I..3I
+        198..=201 => 0x70 + (vector - 198),
+        // SCI0..6 ERIn, RXIn, TXIn, TEIn
+        214..=241 => 0x80 + (vector - 214) / 4,
+        // RIIC0..1 ICEEIn, ICRXIn, ICTXIn, ICTEIn
+        246..=253 => 0x88 + (vector - 246),
+        _ => return None,
+    })
+}
+
 pub mod system {
     use super::*;
 
@@ -573,3 +618,36 @@ pub mod ioport {
         ],
     ];
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ipr_index_known_vectors() {
+        assert_eq!(ipr_index(16), Some(0x00));
+        assert_eq!(ipr_index(27), Some(0x03));
+        assert_eq!(ipr_index(28), Some(0x04));
+        assert_eq!(ipr_index(31), Some(0x07));
+        assert_eq!(ipr_index(64), Some(0x20));
+        assert_eq!(ipr_index(79), Some(0x2f));
+        // TMR0 CMIA0, CMIB0, OVI0 share one register
+        assert_eq!(ipr_index(174), Some(0x68));
+        assert_eq!(ipr_index(176), Some(0x68));
+        assert_eq!(ipr_index(177), Some(0x69));
+        assert_eq!(ipr_index(185), Some(0x6b));
+        assert_eq!(ipr_index(201), Some(0x73));
+        // SCI0 ERI0, RXI0, TXI0, TEI0 share one register
+        assert_eq!(ipr_index(214), Some(0x80));
+        assert_eq!(ipr_index(217), Some(0x80));
+        assert_eq!(ipr_index(241), Some(0x86));
+        assert_eq!(ipr_index(253), Some(0x8f));
+    }
+
+    #[test]
+    fn ipr_index_unknown_vectors() {
+        for vector in [0, 15, 17, 22, 33, 80, 97, 173, 186, 202, 242, 254, 255, 256] {
+            assert_eq!(ipr_index(vector), None, "vector {vector}");
+        }
+    }
+}
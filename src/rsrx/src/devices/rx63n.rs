@@ -6,15 +6,18 @@ use tock_registers::{
     registers::{ReadOnly, ReadWrite},
 };
 
-use crate::{cmt, icua, scia};
+use crate::{cmt, icub, mpc, mpu, scia, tmr};
 
 peripheral_set! {
     /// All the peripherals of RX62N
     pub struct Peripherals {
         pub SYSTEM: SYSTEM,
+        pub MPU: MPU,
         pub ICU: ICU,
         pub CMT0_1: CMT0_1,
         pub CMT2_3: CMT2_3,
+        pub TMR0_1: TMR0_1,
+        pub TMR2_3: TMR2_3,
         pub SCI0: SCI0,
         pub SCI1: SCI1,
         pub SCI2: SCI2,
@@ -29,13 +32,17 @@ peripheral_set! {
         pub SCI11: SCI11,
         pub SCI12: SCI12,
         pub PORTS: PORTS,
+        pub MPC: MPC,
     }
 }
 
 zero_sized_ref!(pub struct SYSTEM: &system::Registers = 0x0008_0000);
-zero_sized_ref!(pub struct ICU: &icua::Registers = 0x0008_7000); // TODO: Actually it's ICUb
+zero_sized_ref!(pub struct MPU: &mpu::Registers = 0x0008_6400);
+zero_sized_ref!(pub struct ICU: &icub::Registers = 0x0008_7000);
 zero_sized_ref!(pub struct CMT0_1: &cmt::Registers = 0x0008_8000);
 zero_sized_ref!(pub struct CMT2_3: &cmt::Registers = 0x0008_8010);
+zero_sized_ref!(pub struct TMR0_1: &tmr::Registers = 0x0008_8200);
+zero_sized_ref!(pub struct TMR2_3: &tmr::Registers = 0x0008_8210);
 zero_sized_ref!(pub struct SCI0: &scia::Registers = 0x0008_a000); // TOOD: Actually it's SCIc
 zero_sized_ref!(pub struct SCI1: &scia::Registers = 0x0008_a020); // TOOD: Actually it's SCIc
 zero_sized_ref!(pub struct SCI2: &scia::Registers = 0x0008_a040); // TOOD: Actually it's SCIc
@@ -50,6 +57,47 @@ zero_sized_ref!(pub struct SCI10: &scia::Registers = 0x0008_a140); // TOOD: Actu
 zero_sized_ref!(pub struct SCI11: &scia::Registers = 0x0008_a160); // TOOD: Actually it's SCIc
 zero_sized_ref!(pub struct SCI12: &scia::Registers = 0x0008_b300); // TOOD: Actually it's SCId
 zero_sized_ref!(pub struct PORTS: &ports::Registers = 0x0008_C000);
+zero_sized_ref!(pub struct MPC: &mpc::Registers = 0x0008_C100);
+
+/// Get the index of the interrupt priority register (`ICU.IPR[_]`) that
+/// controls the priority of the specified interrupt vector.
+///
+/// Returns `None` if the vector is reserved, doesn't have a priority
+/// register, or isn't listed here yet.
+pub const fn ipr_index(vector: usize) -> Option<usize> {
+    Some(match vector {
+        // BSC BUSERR
+        16 => 0,
+        // FCU FIFERR
+        21 => 1,
+        // FCU FRDYI
+        23 => 2,
+        // ICU SWINT
+        27 => 3,
+        // CMT0..3 CMIn
+        28..=31 => 4 + (vector - 28),
+        // ETHER EINT
+        32 => 32,
+        // ICU IRQ0..15
+        64..=79 => vector,
+        // S12AD S12ADI0
+        102 => 102,
+        // ICU GROUP0..6
+        106..=112 => vector,
+        // ICU GROUP12
+        114 => 114,
+        // TMR0..3 CMIAn, CMIBn, OVIn
+        170..=181 => vector - (vector - 170) % 3,
+        // DMAC DMAC0I..3I
+        198..=201 => vector,
+        // The remaining vectors in 128..=207 (e.g., TPU, MTU, POE, and RIIC),
+        // each of which has its own priority register
+        128..=207 => vector,
+        // SCI0..12 RXIn, TXIn, TEIn
+        214..=252 => vector - (vector - 214) % 3,
+        _ => return None,
+    })
+}
 
 pub mod system {
     use super::*;
@@ -810,3 +858,42 @@ pub mod ports {
         ],
     ];
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ipr_index_known_vectors() {
+        assert_eq!(ipr_index(16), Some(0));
+        assert_eq!(ipr_index(27), Some(3));
+        assert_eq!(ipr_index(28), Some(4));
+        assert_eq!(ipr_index(31), Some(7));
+        assert_eq!(ipr_index(32), Some(32));
+        assert_eq!(ipr_index(64), Some(64));
+        assert_eq!(ipr_index(79), Some(79));
+        assert_eq!(ipr_index(106), Some(106));
+        assert_eq!(ipr_index(112), Some(112));
+        assert_eq!(ipr_index(114), Some(114));
+        // TMR0 CMIA0, CMIB0, OVI0 share one register
+        assert_eq!(ipr_index(170), Some(170));
+        assert_eq!(ipr_index(172), Some(170));
+        assert_eq!(ipr_index(173), Some(173));
+        assert_eq!(ipr_index(181), Some(179));
+        assert_eq!(ipr_index(198), Some(198));
+        assert_eq!(ipr_index(201), Some(201));
+        assert_eq!(ipr_index(128), Some(128));
+        assert_eq!(ipr_index(207), Some(207));
+        // SCI0 RXI0, TXI0, TEI0 share one register
+        assert_eq!(ipr_index(214), Some(214));
+        assert_eq!(ipr_index(216), Some(214));
+        assert_eq!(ipr_index(252), Some(250));
+    }
+
+    #[test]
+    fn ipr_index_unknown_vectors() {
+        for vector in [0, 15, 17, 22, 33, 80, 113, 115, 208, 213, 253, 255, 256] {
+            assert_eq!(ipr_index(vector), None, "vector {vector}");
+        }
+    }
+}
@@ -0,0 +1,210 @@
+//! Interrupt Control Unit (ICUb)
+//!
+//! In addition to the features of [ICUa](crate::icua), ICUb supports group
+//! interrupts (`GRPn`, `GENn`, `GCRn`), software-configurable interrupt
+//! sources (`PIBRn`, `SLIBXRn`), and digital filters for `IRQn` and `NMI`.
+use tock_registers::{
+    register_bitfields, register_structs,
+    registers::{ReadOnly, ReadWrite},
+};
+
+pub use crate::icua::{
+    DtcActivationEnable, FastInterrupt, InterruptPriority, InterruptRequest,
+    InterruptRequestEnable, IrqControl, NmiPinInterruptControl, SoftwareInterruptActivation,
+};
+use crate::utils::Align4;
+
+register_structs! {
+    /// The memory-mapped registers exposed by Interrupt Control Unit.
+    pub Registers {
+        /// Interrupt request register
+        (0x0000 => pub ir: [ReadWrite<u8, InterruptRequest::Register>; 256]),
+        /// DTC activation enable register
+        (0x0100 => pub dtcer: [ReadWrite<u8, DtcActivationEnable::Register>; 256]),
+        /// Interrupt request enable register
+        (0x0200 => pub ier: [ReadWrite<u8, InterruptRequestEnable::Register>; 32]),
+        (0x0220 => _pad0),
+        /// Software interrupt activation register
+        (0x02e0 => pub swintr: ReadWrite<u8, SoftwareInterruptActivation::Register>),
+        (0x02e1 => _pad1),
+        /// Fast interrupt set register
+        (0x02f0 => pub fir: ReadWrite<u16, FastInterrupt::Register>),
+        (0x02f2 => _pad2),
+        /// Interrupt source priority register
+        (0x0300 => pub ipr: [ReadWrite<u8, InterruptPriority::Register>; 256]),
+        /// DMACA activation source register
+        (0x0400 => pub dmrsr: [Align4<ReadWrite<u8>>; 4]),
+        (0x0410 => _pad3),
+        /// IRQ control register
+        (0x0500 => pub irqcr: [ReadWrite<u8, IrqControl::Register>; 16]),
+        /// IRQ pin digital filter enable register 0 (`IRQ0`–`IRQ7`)
+        (0x0510 => pub irqflte0: ReadWrite<u8, DigitalFilterEnable::Register>),
+        /// IRQ pin digital filter enable register 1 (`IRQ8`–`IRQ15`)
+        (0x0511 => pub irqflte1: ReadWrite<u8, DigitalFilterEnable::Register>),
+        (0x0512 => _pad4),
+        /// IRQ pin digital filter setting register 0 (`IRQ0`–`IRQ7`)
+        (0x0518 => pub irqfltc0: ReadWrite<u16, DigitalFilterSetting::Register>),
+        /// IRQ pin digital filter setting register 1 (`IRQ8`–`IRQ15`)
+        (0x051a => pub irqfltc1: ReadWrite<u16, DigitalFilterSetting::Register>),
+        (0x051c => _pad5),
+        /// Non-maskable interrupt status register
+        (0x0580 => pub nmisr: ReadOnly<u8, NmiStatus::Register>),
+        /// Non-maskable interrupt enable register
+        (0x0581 => pub nmier: ReadWrite<u8, NmiEnable::Register>),
+        /// Non-maskable interrupt status clear register
+        (0x0582 => pub nmiclr: ReadWrite<u8, NmiClear::Register>),
+        /// NMI pin interrupt control register
+        (0x0583 => pub nmicr: ReadWrite<u8, NmiPinInterruptControl::Register>),
+        (0x0584 => _pad6),
+        /// NMI pin digital filter enable register
+        (0x0590 => pub nmiflte: ReadWrite<u8, NmiDigitalFilterEnable::Register>),
+        (0x0591 => _pad7),
+        /// NMI pin digital filter setting register
+        (0x0594 => pub nmifltc: ReadWrite<u8, NmiDigitalFilterSetting::Register>),
+        (0x0595 => _pad8),
+        /// Group `n` interrupt source register (`GRP00`–`GRP12`). Each bit
+        /// reflects the request state of the corresponding interrupt source.
+        (0x0600 => pub grp: [ReadOnly<u32>; 13]),
+        (0x0634 => _pad9),
+        /// Group `n` interrupt enable register (`GEN00`–`GEN12`)
+        (0x0640 => pub gen: [ReadWrite<u32>; 13]),
+        (0x0674 => _pad10),
+        /// Group `n` interrupt clear register (`GCR00`–`GCR12`). Only valid for
+        /// edge-detected interrupt sources.
+        (0x0680 => pub gcr: [ReadWrite<u32>; 13]),
+        (0x06b4 => _pad11),
+        /// Software-configurable interrupt B request register (unit selection)
+        (0x0700 => pub pibr: [ReadWrite<u8>; 11]),
+        (0x070b => _pad12),
+        /// Software-configurable interrupt B request register (`SLIBXR128`–
+        /// `SLIBXR207`). `slixr[n - 128]` selects the interrupt source of the
+        /// vector `n`.
+        (0x0780 => pub slixr: [ReadWrite<u8>; 80]),
+        (0x07d0 => @END),
+    }
+}
+
+register_bitfields![u8,
+    pub DigitalFilterEnable [
+        /// Digital filter enable 0
+        FLTEN0 OFFSET(0) NUMBITS(1) [],
+        /// Digital filter enable 1
+        FLTEN1 OFFSET(1) NUMBITS(1) [],
+        /// Digital filter enable 2
+        FLTEN2 OFFSET(2) NUMBITS(1) [],
+        /// Digital filter enable 3
+        FLTEN3 OFFSET(3) NUMBITS(1) [],
+        /// Digital filter enable 4
+        FLTEN4 OFFSET(4) NUMBITS(1) [],
+        /// Digital filter enable 5
+        FLTEN5 OFFSET(5) NUMBITS(1) [],
+        /// Digital filter enable 6
+        FLTEN6 OFFSET(6) NUMBITS(1) [],
+        /// Digital filter enable 7
+        FLTEN7 OFFSET(7) NUMBITS(1) [],
+    ],
+
+    pub NmiStatus [
+        /// NMI status flag
+        NMIST OFFSET(0) NUMBITS(1) [
+            NotRequested = 0,
+            Requested = 1,
+        ],
+        /// Oscillation stop detection interrupt status flag
+        OSTST OFFSET(1) NUMBITS(1) [
+            NotRequested = 0,
+            Requested = 1,
+        ],
+        /// Voltage monitoring 1 interrupt status flag
+        LVD1ST OFFSET(2) NUMBITS(1) [
+            NotRequested = 0,
+            Requested = 1,
+        ],
+        /// Voltage monitoring 2 interrupt status flag
+        LVD2ST OFFSET(3) NUMBITS(1) [
+            NotRequested = 0,
+            Requested = 1,
+        ],
+    ],
+
+    pub NmiEnable [
+        /// NMI pin interrupt enable
+        NMIEN OFFSET(0) NUMBITS(1) [
+            Disable = 0,
+            Enable = 1,
+        ],
+        /// Oscillation stop detection interrupt enable
+        OSTEN OFFSET(1) NUMBITS(1) [
+            Disable = 0,
+            Enable = 1,
+        ],
+        /// Voltage monitoring 1 interrupt enable
+        LVD1EN OFFSET(2) NUMBITS(1) [
+            Disable = 0,
+            Enable = 1,
+        ],
+        /// Voltage monitoring 2 interrupt enable
+        LVD2EN OFFSET(3) NUMBITS(1) [
+            Disable = 0,
+            Enable = 1,
+        ],
+    ],
+
+    pub NmiClear [
+        /// NMI clear
+        NMICLR OFFSET(0) NUMBITS(1) [
+            Clear = 1,
+        ],
+        /// OST clear
+        OSTCLR OFFSET(1) NUMBITS(1) [
+            Clear = 1,
+        ],
+        /// LVD1 clear
+        LVD1CLR OFFSET(2) NUMBITS(1) [
+            Clear = 1,
+        ],
+        /// LVD2 clear
+        LVD2CLR OFFSET(3) NUMBITS(1) [
+            Clear = 1,
+        ],
+    ],
+
+    pub NmiDigitalFilterEnable [
+        /// NMI digital filter enable
+        NFLTEN OFFSET(0) NUMBITS(1) [
+            Disable = 0,
+            Enable = 1,
+        ],
+    ],
+
+    pub NmiDigitalFilterSetting [
+        /// NMI digital filter sampling clock
+        NFCLKSEL OFFSET(0) NUMBITS(2) [
+            PclkDiv1 = 0b00,
+            PclkDiv8 = 0b01,
+            PclkDiv32 = 0b10,
+            PclkDiv64 = 0b11,
+        ],
+    ],
+];
+
+register_bitfields![u16,
+    pub DigitalFilterSetting [
+        /// Digital filter sampling clock for pin 0
+        FCLKSEL0 OFFSET(0) NUMBITS(2) [],
+        /// Digital filter sampling clock for pin 1
+        FCLKSEL1 OFFSET(2) NUMBITS(2) [],
+        /// Digital filter sampling clock for pin 2
+        FCLKSEL2 OFFSET(4) NUMBITS(2) [],
+        /// Digital filter sampling clock for pin 3
+        FCLKSEL3 OFFSET(6) NUMBITS(2) [],
+        /// Digital filter sampling clock for pin 4
+        FCLKSEL4 OFFSET(8) NUMBITS(2) [],
+        /// Digital filter sampling clock for pin 5
+        FCLKSEL5 OFFSET(10) NUMBITS(2) [],
+        /// Digital filter sampling clock for pin 6
+        FCLKSEL6 OFFSET(12) NUMBITS(2) [],
+        /// Digital filter sampling clock for pin 7
+        FCLKSEL7 OFFSET(14) NUMBITS(2) [],
+    ],
+];
@@ -0,0 +1,228 @@
+//! Interrupt Control Unit (ICUd)
+//!
+//! ICUd is found in RXv2 and later devices (e.g., RX64M and RX65N). Compared to
+//! [ICUb](crate::icub), it has the second software interrupt (`SWINT2`),
+//! separate group interrupts for edge-detected (`BE0`), level-detected
+//! (`BL0`–`BL2`), and peripheral-clock A (`AL0`–`AL1`) sources, and
+//! software-configurable interrupt sources A and B (`SLIBXRn`, `SLIBRn`,
+//! `SLIARn`).
+use tock_registers::{
+    register_bitfields, register_structs,
+    registers::{ReadOnly, ReadWrite},
+};
+
+pub use crate::icub::{
+    DigitalFilterEnable, DigitalFilterSetting, DtcActivationEnable, FastInterrupt,
+    InterruptPriority, InterruptRequest, InterruptRequestEnable, IrqControl,
+    NmiDigitalFilterEnable, NmiDigitalFilterSetting, NmiPinInterruptControl,
+    SoftwareInterruptActivation,
+};
+use crate::utils::Align4;
+
+register_structs! {
+    /// The memory-mapped registers exposed by Interrupt Control Unit.
+    pub Registers {
+        /// Interrupt request register
+        (0x0000 => pub ir: [ReadWrite<u8, InterruptRequest::Register>; 256]),
+        /// DTC activation enable register
+        (0x0100 => pub dtcer: [ReadWrite<u8, DtcActivationEnable::Register>; 256]),
+        /// Interrupt request enable register
+        (0x0200 => pub ier: [ReadWrite<u8, InterruptRequestEnable::Register>; 32]),
+        (0x0220 => _pad0),
+        /// Software interrupt activation register
+        (0x02e0 => pub swintr: ReadWrite<u8, SoftwareInterruptActivation::Register>),
+        /// Software interrupt 2 activation register
+        (0x02e1 => pub swint2r: ReadWrite<u8, SoftwareInterrupt2Activation::Register>),
+        (0x02e2 => _pad1),
+        /// Fast interrupt set register
+        (0x02f0 => pub fir: ReadWrite<u16, FastInterrupt::Register>),
+        (0x02f2 => _pad2),
+        /// Interrupt source priority register
+        (0x0300 => pub ipr: [ReadWrite<u8, InterruptPriority::Register>; 256]),
+        /// DMACA activation source register
+        (0x0400 => pub dmrsr: [Align4<ReadWrite<u8>>; 8]),
+        (0x0420 => _pad3),
+        /// IRQ control register
+        (0x0500 => pub irqcr: [ReadWrite<u8, IrqControl::Register>; 16]),
+        /// IRQ pin digital filter enable register 0 (`IRQ0`–`IRQ7`)
+        (0x0510 => pub irqflte0: ReadWrite<u8, DigitalFilterEnable::Register>),
+        /// IRQ pin digital filter enable register 1 (`IRQ8`–`IRQ15`)
+        (0x0511 => pub irqflte1: ReadWrite<u8, DigitalFilterEnable::Register>),
+        (0x0512 => _pad4),
+        /// IRQ pin digital filter setting register 0 (`IRQ0`–`IRQ7`)
+        (0x0518 => pub irqfltc0: ReadWrite<u16, DigitalFilterSetting::Register>),
+        /// IRQ pin digital filter setting register 1 (`IRQ8`–`IRQ15`)
+        (0x051a => pub irqfltc1: ReadWrite<u16, DigitalFilterSetting::Register>),
+        (0x051c => _pad5),
+        /// Non-maskable interrupt status register
+        (0x0580 => pub nmisr: ReadOnly<u8, NmiStatus::Register>),
+        /// Non-maskable interrupt enable register
+        (0x0581 => pub nmier: ReadWrite<u8, NmiEnable::Register>),
+        /// Non-maskable interrupt status clear register
+        (0x0582 => pub nmiclr: ReadWrite<u8, NmiClear::Register>),
+        /// NMI pin interrupt control register
+        (0x0583 => pub nmicr: ReadWrite<u8, NmiPinInterruptControl::Register>),
+        (0x0584 => _pad6),
+        /// NMI pin digital filter enable register
+        (0x0590 => pub nmiflte: ReadWrite<u8, NmiDigitalFilterEnable::Register>),
+        (0x0591 => _pad7),
+        /// NMI pin digital filter setting register
+        (0x0594 => pub nmifltc: ReadWrite<u8, NmiDigitalFilterSetting::Register>),
+        (0x0595 => _pad8),
+        /// Group BE0 interrupt request register
+        (0x0600 => pub grpbe0: ReadOnly<u32>),
+        (0x0604 => _pad9),
+        /// Group BL0–BL2 interrupt request registers
+        (0x0630 => pub grpbl: [ReadOnly<u32>; 3]),
+        (0x063c => _pad10),
+        /// Group BE0 interrupt request enable register
+        (0x0640 => pub genbe0: ReadWrite<u32>),
+        (0x0644 => _pad11),
+        /// Group BL0–BL2 interrupt request enable registers
+        (0x0670 => pub genbl: [ReadWrite<u32>; 3]),
+        (0x067c => _pad12),
+        /// Group BE0 interrupt clear register
+        (0x0680 => pub gcrbe0: ReadWrite<u32>),
+        (0x0684 => _pad13),
+        /// Software configurable interrupt B request register (unit selection)
+        (0x0700 => pub pibr: [ReadWrite<u8>; 11]),
+        (0x070b => _pad14),
+        /// Software configurable interrupt request registers (`SLIBXR128`–
+        /// `SLIBXR143`, `SLIBR144`–`SLIBR207`, and `SLIAR208`–`SLIAR255`).
+        /// `slixr[n - 128]` selects the interrupt source of the vector `n`.
+        (0x0780 => pub slixr: [ReadWrite<u8>; 128]),
+        (0x0800 => _pad15),
+        /// Group AL0–AL1 interrupt request registers
+        (0x0830 => pub grpal: [ReadOnly<u32>; 2]),
+        (0x0838 => _pad16),
+        /// Group AL0–AL1 interrupt request enable registers
+        (0x0870 => pub genal: [ReadWrite<u32>; 2]),
+        (0x0878 => _pad17),
+        /// Software configurable interrupt A request register (unit selection)
+        (0x0900 => pub piar: [ReadWrite<u8>; 12]),
+        (0x090c => _pad18),
+        /// Software configurable interrupt request register write protect
+        /// register
+        (0x0a00 => pub slipprcr: ReadWrite<u8, WriteProtect::Register>),
+        (0x0a01 => @END),
+    }
+}
+
+register_bitfields![u8,
+    pub SoftwareInterrupt2Activation [
+        SWINT2 OFFSET(0) NUMBITS(1) [
+            Request = 1,
+        ],
+    ],
+
+    pub NmiStatus [
+        /// NMI pin interrupt status flag
+        NMIST OFFSET(0) NUMBITS(1) [
+            NotRequested = 0,
+            Requested = 1,
+        ],
+        /// Oscillation stop detection interrupt status flag
+        OSTST OFFSET(1) NUMBITS(1) [
+            NotRequested = 0,
+            Requested = 1,
+        ],
+        /// WDT underflow/refresh error status flag
+        WDTST OFFSET(2) NUMBITS(1) [
+            NotRequested = 0,
+            Requested = 1,
+        ],
+        /// IWDT underflow/refresh error status flag
+        IWDTST OFFSET(3) NUMBITS(1) [
+            NotRequested = 0,
+            Requested = 1,
+        ],
+        /// Voltage monitoring 1 interrupt status flag
+        LVD1ST OFFSET(4) NUMBITS(1) [
+            NotRequested = 0,
+            Requested = 1,
+        ],
+        /// Voltage monitoring 2 interrupt status flag
+        LVD2ST OFFSET(5) NUMBITS(1) [
+            NotRequested = 0,
+            Requested = 1,
+        ],
+        /// RAM error interrupt status flag
+        RAMST OFFSET(6) NUMBITS(1) [
+            NotRequested = 0,
+            Requested = 1,
+        ],
+    ],
+
+    pub NmiEnable [
+        /// NMI pin interrupt enable
+        NMIEN OFFSET(0) NUMBITS(1) [
+            Disable = 0,
+            Enable = 1,
+        ],
+        /// Oscillation stop detection interrupt enable
+        OSTEN OFFSET(1) NUMBITS(1) [
+            Disable = 0,
+            Enable = 1,
+        ],
+        /// WDT underflow/refresh error enable
+        WDTEN OFFSET(2) NUMBITS(1) [
+            Disable = 0,
+            Enable = 1,
+        ],
+        /// IWDT underflow/refresh error enable
+        IWDTEN OFFSET(3) NUMBITS(1) [
+            Disable = 0,
+            Enable = 1,
+        ],
+        /// Voltage monitoring 1 interrupt enable
+        LVD1EN OFFSET(4) NUMBITS(1) [
+            Disable = 0,
+            Enable = 1,
+        ],
+        /// Voltage monitoring 2 interrupt enable
+        LVD2EN OFFSET(5) NUMBITS(1) [
+            Disable = 0,
+            Enable = 1,
+        ],
+        /// RAM error interrupt enable
+        RAMEN OFFSET(6) NUMBITS(1) [
+            Disable = 0,
+            Enable = 1,
+        ],
+    ],
+
+    pub NmiClear [
+        /// NMI clear
+        NMICLR OFFSET(0) NUMBITS(1) [
+            Clear = 1,
+        ],
+        /// OST clear
+        OSTCLR OFFSET(1) NUMBITS(1) [
+            Clear = 1,
+        ],
+        /// WDT clear
+        WDTCLR OFFSET(2) NUMBITS(1) [
+            Clear = 1,
+        ],
+        /// IWDT clear
+        IWDTCLR OFFSET(3) NUMBITS(1) [
+            Clear = 1,
+        ],
+        /// LVD1 clear
+        LVD1CLR OFFSET(4) NUMBITS(1) [
+            Clear = 1,
+        ],
+        /// LVD2 clear
+        LVD2CLR OFFSET(5) NUMBITS(1) [
+            Clear = 1,
+        ],
+    ],
+
+    pub WriteProtect [
+        /// Software configurable interrupt request register write protect
+        WPRC OFFSET(0) NUMBITS(1) [
+            WriteEnabled = 0,
+            WriteProtected = 1,
+        ],
+    ],
+];
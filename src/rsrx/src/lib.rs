@@ -6,9 +6,15 @@
 mod macros;
 
 pub mod cmt;
+pub mod cmtw;
 pub mod icua;
+pub mod icub;
+pub mod icud;
+pub mod mpc;
+pub mod mpu;
 pub mod ports;
 pub mod scia;
+pub mod tmr;
 mod utils;
 
 /// I/O register memory mappings for RX microcontrollers
@@ -0,0 +1,46 @@
+//! Multi-Function Pin Controller (MPC)
+use tock_registers::{register_bitfields, register_structs, registers::ReadWrite};
+
+register_structs! {
+    /// The memory-mapped registers exposed by Multi-Function Pin Controller.
+    pub Registers {
+        (0x0000 => _pad0),
+        /// Write-protect register
+        (0x001f => pub pwpr: ReadWrite<u8, WriteProtect::Register>),
+        (0x0020 => _pad1),
+        /// Pin function control register. `pfs[m * 8 + n]` is `PmnPFS`, where
+        /// ports `PA`–`PJ` are numbered `10`–`18`.
+        (0x0040 => pub pfs: [ReadWrite<u8, PinFunctionControl::Register>; 152]),
+        (0x00d8 => @END),
+    }
+}
+
+register_bitfields![u8,
+    pub WriteProtect [
+        /// `PFSWE` bit write disable
+        B0WI OFFSET(7) NUMBITS(1) [
+            WriteEnabled = 0,
+            WriteDisabled = 1,
+        ],
+        /// `PFS` register write enable
+        PFSWE OFFSET(6) NUMBITS(1) [
+            WriteDisabled = 0,
+            WriteEnabled = 1,
+        ],
+    ],
+
+    pub PinFunctionControl [
+        /// Peripheral function select
+        PSEL OFFSET(0) NUMBITS(6) [],
+        /// Interrupt input function select
+        ISEL OFFSET(6) NUMBITS(1) [
+            NotUsed = 0,
+            UsedAsIrq = 1,
+        ],
+        /// Analog function select
+        ASEL OFFSET(7) NUMBITS(1) [
+            NotUsed = 0,
+            UsedAsAnalog = 1,
+        ],
+    ],
+];
@@ -0,0 +1,129 @@
+//! Memory-Protection Unit
+use tock_registers::{
+    register_bitfields, register_structs,
+    registers::{ReadOnly, ReadWrite},
+};
+
+register_structs! {
+    /// The memory-mapped registers exposed by Memory-Protection Unit.
+    pub Registers {
+        /// Region settings
+        (0x000 => pub regions: [region::Registers; 8]),
+        (0x040 => _pad0),
+        /// Memory-protection enable register
+        (0x100 => pub mpen: ReadWrite<u32, Enable::Register>),
+        /// Background access control register
+        (0x104 => pub mpbac: ReadWrite<u32, BackgroundAccessControl::Register>),
+        /// Memory-protection error status-clearing register
+        (0x108 => pub mpeclr: ReadWrite<u32, ErrorStatusClear::Register>),
+        /// Memory-protection error status register
+        (0x10c => pub mpests: ReadOnly<u32, ErrorStatus::Register>),
+        (0x110 => _pad1),
+        /// Data memory-protection error address register
+        (0x114 => pub mpdea: ReadOnly<u32>),
+        (0x118 => _pad2),
+        /// Region search address register
+        (0x120 => pub mpsa: ReadWrite<u32>),
+        /// Region search operation register
+        (0x124 => pub mpops: ReadWrite<u16, SearchOperation::Register>),
+        /// Region invalidation operation register
+        (0x126 => pub mpopi: ReadWrite<u16, InvalidationOperation::Register>),
+        /// Instruction-hit region register
+        (0x128 => pub mhiti: ReadOnly<u32, HitRegion::Register>),
+        /// Data-hit region register
+        (0x12c => pub mhitd: ReadOnly<u32, HitRegion::Register>),
+        (0x130 => @END),
+    }
+}
+
+pub mod region {
+    use super::*;
+
+    register_structs! {
+        /// The memory-mapped registers specifying one region of
+        /// Memory-Protection Unit.
+        pub Registers {
+            /// Region start page number register
+            (0x0 => pub rspage: ReadWrite<u32, StartPage::Register>),
+            /// Region end page number register
+            (0x4 => pub repage: ReadWrite<u32, EndPage::Register>),
+            (0x8 => @END),
+        }
+    }
+}
+
+register_bitfields![u32,
+    pub StartPage [
+        /// Region start page number (bits 31–4 of the start address)
+        RSPN OFFSET(4) NUMBITS(28) [],
+    ],
+
+    pub EndPage [
+        /// Region valid
+        V OFFSET(0) NUMBITS(1) [
+            Invalid = 0,
+            Valid = 1,
+        ],
+        /// User-mode execution permission
+        UAC0 OFFSET(1) NUMBITS(1) [],
+        /// User-mode write permission
+        UAC1 OFFSET(2) NUMBITS(1) [],
+        /// User-mode read permission
+        UAC2 OFFSET(3) NUMBITS(1) [],
+        /// Region end page number (bits 31–4 of the last page's address)
+        REPN OFFSET(4) NUMBITS(28) [],
+    ],
+
+    pub Enable [
+        /// Memory-protection enable
+        MPEN OFFSET(0) NUMBITS(1) [
+            Disable = 0,
+            Enable = 1,
+        ],
+    ],
+
+    pub BackgroundAccessControl [
+        /// User-mode execution permission
+        UBAC0 OFFSET(1) NUMBITS(1) [],
+        /// User-mode write permission
+        UBAC1 OFFSET(2) NUMBITS(1) [],
+        /// User-mode read permission
+        UBAC2 OFFSET(3) NUMBITS(1) [],
+    ],
+
+    pub ErrorStatusClear [
+        /// Clear memory-protection error status
+        CLR OFFSET(0) NUMBITS(1) [],
+    ],
+
+    pub ErrorStatus [
+        /// Instruction memory-protection error generated
+        IMPER OFFSET(0) NUMBITS(1) [],
+        /// Data memory-protection error generated
+        DMPER OFFSET(1) NUMBITS(1) [],
+        /// Data memory-protection error type
+        DRW OFFSET(2) NUMBITS(1) [
+            Read = 0,
+            Write = 1,
+        ],
+    ],
+
+    pub HitRegion [
+        /// Hit region (bit n is set if region n was hit)
+        HIT OFFSET(0) NUMBITS(8) [],
+        /// User-mode access permissions of the hit regions
+        UHAC OFFSET(16) NUMBITS(3) [],
+    ],
+];
+
+register_bitfields![u16,
+    pub SearchOperation [
+        /// Region search
+        S OFFSET(0) NUMBITS(1) [],
+    ],
+
+    pub InvalidationOperation [
+        /// Instruction-hit region invalidation
+        INV OFFSET(0) NUMBITS(1) [],
+    ],
+];
@@ -0,0 +1,92 @@
+//! 8-Bit Timer
+//!
+//! A TMR unit consists of two 8-bit channels (e.g., TMR0 and TMR1), whose
+//! registers are interleaved. `tcr[0]` belongs to the even-numbered channel,
+//! and `tcr[1]` to the odd-numbered one. The two channels can be cascaded to
+//! form a 16-bit timer by setting the even-numbered channel's `TCCR.CSS` to
+//! [`CounterControl::CSS::OddChannelOverflow`], in which case the
+//! even-numbered channel holds the upper 8 bits.
+use tock_registers::{register_bitfields, register_structs, registers::ReadWrite};
+
+register_structs! {
+    /// The memory-mapped registers exposed by a unit of 8-Bit Timer.
+    pub Registers {
+        /// Timer control register
+        (0x00 => pub tcr: [ReadWrite<u8, Control::Register>; 2]),
+        /// Timer control/status register
+        (0x02 => pub tcsr: [ReadWrite<u8, ControlStatus::Register>; 2]),
+        /// Time constant register A
+        (0x04 => pub tcora: [ReadWrite<u8>; 2]),
+        /// Time constant register B
+        (0x06 => pub tcorb: [ReadWrite<u8>; 2]),
+        /// Timer counter
+        (0x08 => pub tcnt: [ReadWrite<u8>; 2]),
+        /// Timer counter control register
+        (0x0a => pub tccr: [ReadWrite<u8, CounterControl::Register>; 2]),
+        (0x0c => _pad0),
+        (0x10 => @END),
+    }
+}
+
+register_bitfields![u8,
+    pub Control [
+        /// Counter clear
+        CCLR OFFSET(3) NUMBITS(2) [
+            NotCleared = 0b00,
+            ClearedByCompareMatchA = 0b01,
+            ClearedByCompareMatchB = 0b10,
+            ClearedByExternalReset = 0b11,
+        ],
+        /// Timer overflow interrupt enable
+        OVIE OFFSET(5) NUMBITS(1) [],
+        /// Compare match interrupt enable A
+        CMIEA OFFSET(6) NUMBITS(1) [],
+        /// Compare match interrupt enable B
+        CMIEB OFFSET(7) NUMBITS(1) [],
+    ],
+
+    pub ControlStatus [
+        /// Output select A
+        OSA OFFSET(0) NUMBITS(2) [
+            NoChange = 0b00,
+            Low = 0b01,
+            High = 0b10,
+            Toggle = 0b11,
+        ],
+        /// Output select B
+        OSB OFFSET(2) NUMBITS(2) [
+            NoChange = 0b00,
+            Low = 0b01,
+            High = 0b10,
+            Toggle = 0b11,
+        ],
+        /// A/D trigger enable (even-numbered channels only)
+        ADTE OFFSET(4) NUMBITS(1) [],
+    ],
+
+    pub CounterControl [
+        /// Clock select. The meaning depends on `CSS`. The values listed here
+        /// are for `CSS == InternalClock`.
+        CKS OFFSET(0) NUMBITS(3) [
+            Pclk = 0b000,
+            PclkDividedBy2 = 0b001,
+            PclkDividedBy8 = 0b010,
+            PclkDividedBy32 = 0b011,
+            PclkDividedBy64 = 0b100,
+            PclkDividedBy1024 = 0b101,
+            PclkDividedBy8192 = 0b110,
+        ],
+        /// Clock source select
+        CSS OFFSET(3) NUMBITS(2) [
+            ExternalClock = 0b00,
+            InternalClock = 0b01,
+            /// Even-numbered channel: Count at the odd-numbered channel's
+            /// overflow (16-bit count mode). Odd-numbered channel: Count at
+            /// the even-numbered channel's compare match A (compare match
+            /// count mode).
+            OddChannelOverflow = 0b11,
+        ],
+        /// Timer reset detection condition select
+        TMRIS OFFSET(7) NUMBITS(1) [],
+    ],
+];